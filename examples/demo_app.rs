@@ -26,9 +26,11 @@ impl SystemThemeDemoApp {
         // std::env::remove_var("XDG_CURRENT_DESKTOP");
         // std::env::remove_var("DESKTOP_SESSION");
 
-        // Here i'm unwrapping system_theme() for testing purposes,
+        // Here i'm unwrapping SystemTheme::load() for testing purposes,
         // but you should probably print out or handle the error gracefully in your app.
-        cc.egui_ctx.set_style(egui_system_theme::system_theme().unwrap());
+        egui_system_theme::SystemTheme::load()
+            .unwrap()
+            .apply(&cc.egui_ctx);
 
         Self::default()
     }
@@ -52,7 +54,7 @@ impl App for SystemThemeDemoApp {
                         ctx.set_style(Style::default());
                     }
                     if ui.button("System").clicked() {
-                        ctx.set_style(egui_system_theme::system_theme().unwrap());
+                        egui_system_theme::SystemTheme::load().unwrap().apply(ctx);
                     }
                 });
             });
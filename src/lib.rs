@@ -1,7 +1,10 @@
-use std::error::Error;
+use std::{collections::HashMap, error::Error, sync::Arc};
 
 pub use dark_light;
-pub(crate) use egui::*;
+pub(crate) use egui::{
+    style::{ScrollStyle, WidgetVisuals},
+    *,
+};
 pub(crate) use once_cell::sync::Lazy;
 
 #[cfg(target_os = "windows")]
@@ -22,29 +25,494 @@ use linux as platform;
 /// Caching whether the system is running dark mode or light mode so we don't have to detect it more then once.
 pub(crate) static DARK_LIGHT_MODE: Lazy<dark_light::Mode> = Lazy::new(dark_light::detect);
 
+/// A system theme: the egui [Style] it has, and how the widgets egui's style has no place for look.
+///
+/// [SystemTheme::apply] sets its style on a [Context], and lets the helpers in this crate, like [menu_button],
+/// find the rest of it while the context has that style.
+#[derive(Clone, Debug)]
+pub struct SystemTheme {
+    pub style: Style,
+    /// How menu items look when hovered.
+    /// egui draws them with the same visuals as buttons, so [menu_button] and [context_menu] swap these in.
+    pub menu_items: Option<MenuItemVisuals>,
+    /// How tooltips look. egui draws them like menus, so [on_hover_ui] and [on_hover_text] draw their own.
+    pub tooltip: Option<TooltipVisuals>,
+    /// How sliders, progress bars and spinners look.
+    /// egui paints them with colors other widgets share, so [slider], [progress_bar] and [spinner] swap these in.
+    pub range: Option<RangeVisuals>,
+    /// How menubars in [titlebar_extension] look, styled like the theme's flat toolbar buttons.
+    pub menubar: Option<MenubarVisuals>,
+    /// The [ThemeClass]es the theme has styles for.
+    pub classes: ThemeClasses,
+    /// The visuals for each [ColorSet] the theme has.
+    pub color_sets: HashMap<ColorSet, Visuals>,
+    /// The outline around focused widgets. egui draws them like pressed ones, so [focus_outline] paints it around them.
+    pub focus_outline: Option<Stroke>,
+}
+
+impl SystemTheme {
+    /// A theme with only `style`, for the backends to fill in.
+    pub fn new(style: Style) -> Self {
+        Self {
+            style,
+            menu_items: None,
+            tooltip: None,
+            range: None,
+            menubar: None,
+            classes: ThemeClasses::new(),
+            color_sets: HashMap::new(),
+            focus_outline: None,
+        }
+    }
+
+    /// Loads the system theme, on top of egui's own style for the system's dark or light mode.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let mut theme = Self::new(base_style());
+        platform::style_theme(&mut theme)?;
+        Ok(theme)
+    }
+
+    /// Like [SystemTheme::load], but for when the window isn't focused, which only some themes style differently.
+    pub fn load_unfocused() -> Result<Self, Box<dyn Error>> {
+        #[cfg(target_os = "linux")]
+        {
+            let mut theme = Self::new(base_style());
+            linux::style_unfocused_theme(&mut theme)?;
+            Ok(theme)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        Self::load()
+    }
+
+    /// Sets the context's style to this theme's, and registers the theme, see [SystemTheme::register].
+    pub fn apply(&self, ctx: &Context) {
+        ctx.set_style(self.style.clone());
+        self.register(ctx);
+    }
+
+    /// Lets the helpers find this theme whenever the context has its visuals, without changing the context's style.
+    /// Register both the focused and unfocused themes to switch between them with [Context::set_style].
+    pub fn register(&self, ctx: &Context) {
+        let theme = Arc::new(self.clone());
+        ctx.data_mut(|data| {
+            let themes = data.get_temp_mut_or_default::<Vec<Arc<Self>>>(Self::id());
+            themes.retain(|registered| registered.style.visuals != theme.style.visuals);
+            themes.push(theme);
+        });
+    }
+
+    /// The registered theme the context's visuals are from, or [None] if it doesn't have one's,
+    /// like when the app changed the visuals after applying the theme.
+    pub fn current(ctx: &Context) -> Option<Arc<Self>> {
+        let style = ctx.style();
+        ctx.data(|data| {
+            data.get_temp::<Vec<Arc<Self>>>(Self::id())?
+                .into_iter()
+                .find(|theme| theme.style.visuals == style.visuals)
+        })
+    }
+
+    fn id() -> Id {
+        Id::new("egui_system_theme::SystemTheme")
+    }
+
+    /// Modifies `style` with a backend, for the functions that only give back a [Style].
+    pub(crate) fn style_with<R>(
+        style: &mut Style,
+        backend: impl FnOnce(&mut Self) -> Result<R, Box<dyn Error>>,
+    ) -> Result<R, Box<dyn Error>> {
+        let mut theme = Self::new(style.clone());
+        let result = backend(&mut theme)?;
+        *style = theme.style;
+        Ok(result)
+    }
+
+    /// Makes hovered widgets look like this theme's hovered menu items, for the inside of a menu.
+    /// Does nothing if the theme doesn't have them.
+    pub fn style_menu_items(&self, style: &mut Style, context_menu: bool) {
+        if let Some(items) = self.menu_items {
+            let hovered = if context_menu {
+                items.context_hovered
+            } else {
+                items.hovered
+            };
+            // egui takes the strokes off menu items
+            style.visuals.widgets.hovered = WidgetVisuals {
+                bg_stroke: Stroke::NONE,
+                ..hovered
+            };
+        }
+    }
+
+    /// Makes popups look like this theme's tooltips, for the inside of a tooltip.
+    /// Does nothing if the theme doesn't have them.
+    pub fn style_tooltip(&self, style: &mut Style) {
+        if let Some(tooltip) = self.tooltip {
+            style.visuals.window_fill = tooltip.fill;
+            style.visuals.widgets.noninteractive.fg_stroke.color = tooltip.text_color;
+            style.visuals.window_stroke = tooltip.stroke;
+            style.visuals.menu_rounding = tooltip.rounding;
+            style.spacing.menu_margin = tooltip.margin;
+            style.visuals.popup_shadow = tooltip.shadow;
+        }
+    }
+
+    /// Makes sliders and progress bars look like this theme's.
+    /// egui paints their rails with colors shared by check boxes, text edits and selections, so this is meant for a scope around them.
+    /// Does nothing if the theme doesn't have them.
+    pub fn style_range(&self, style: &mut Style) {
+        if let Some(range) = self.range {
+            let visuals = &mut style.visuals;
+            visuals.widgets.inactive.bg_fill = range.trough;
+            visuals.widgets.hovered.bg_fill = range.handle;
+            visuals.widgets.active.bg_fill = range.handle_active;
+            visuals.selection.bg_fill = range.highlight;
+            visuals.slider_trailing_fill = true;
+            visuals.extreme_bg_color = range.progress_trough;
+        }
+    }
+
+    /// Makes buttons look like this theme's menubar items. Does nothing if the theme doesn't have them.
+    pub fn style_menubar(&self, style: &mut Style) {
+        if let Some(menubar) = self.menubar {
+            style.visuals.widgets.inactive = menubar.inactive;
+            style.visuals.widgets.hovered = menubar.hovered;
+            style.visuals.widgets.active = menubar.active;
+            style.visuals.widgets.open = menubar.open;
+            style.spacing.button_padding = menubar.button_padding;
+            if let (Some(size), Some(font)) = (
+                menubar.font_size,
+                style.text_styles.get_mut(&TextStyle::Button),
+            ) {
+                font.size = size;
+            }
+        }
+    }
+
+    /// Makes buttons and text look like they have this theme's `class`, see [ThemeClass].
+    /// Does nothing if the theme doesn't have it.
+    pub fn style_class(&self, style: &mut Style, class: ThemeClass) {
+        if let Some(class) = self.classes.get(&class) {
+            style.visuals.widgets.inactive = class.inactive;
+            style.visuals.widgets.hovered = class.hovered;
+            style.visuals.widgets.active = class.active;
+            style.visuals.widgets.noninteractive.fg_stroke.color = class.text_color;
+        }
+    }
+
+    /// Paints this theme's focus outline around `response` if it has focus.
+    pub fn paint_focus_outline(&self, ui: &Ui, response: &Response) {
+        if let Some(stroke) = self.focus_outline.filter(|_| response.has_focus()) {
+            let rounding = ui.style().interact(response).rounding + Rounding::same(stroke.width);
+            ui.painter()
+                .rect_stroke(response.rect.expand(stroke.width), rounding, stroke);
+        }
+    }
+
+    /// Makes widgets look like they're drawn on this theme's `set`, see [ColorSet].
+    /// Does nothing if the theme doesn't have it.
+    pub fn style_color_set(&self, style: &mut Style, set: ColorSet) {
+        if let Some(visuals) = self.color_sets.get(&set) {
+            style.visuals = visuals.clone();
+        }
+    }
+}
+
+/// How a theme's menu items look, see [SystemTheme::menu_items].
+#[derive(Clone, Copy, Debug)]
+pub struct MenuItemVisuals {
+    pub hovered: WidgetVisuals,
+    /// Some themes style context menus differently.
+    pub context_hovered: WidgetVisuals,
+}
+
+/// How a theme's menubars look, see [SystemTheme::menubar].
+#[derive(Clone, Copy, Debug)]
+pub struct MenubarVisuals {
+    /// The titlebar behind the menubar when the window is focused.
+    pub fill: Color32,
+    pub inactive: WidgetVisuals,
+    pub hovered: WidgetVisuals,
+    pub active: WidgetVisuals,
+    /// The item whose menu is open.
+    pub open: WidgetVisuals,
+    pub button_padding: Vec2,
+    /// Some desktops have a separate toolbar font.
+    pub font_size: Option<f32>,
+}
+
+impl MenubarVisuals {
+    /// Flat versions of the buttons in `style`, for the backends to change what their themes have.
+    pub(crate) fn from_style(style: &Style) -> Self {
+        let widgets = &style.visuals.widgets;
+        Self {
+            fill: style.visuals.titlebar(true),
+            inactive: WidgetVisuals {
+                weak_bg_fill: Color32::TRANSPARENT,
+                bg_stroke: Stroke::NONE,
+                ..widgets.inactive
+            },
+            hovered: WidgetVisuals {
+                bg_stroke: Stroke::NONE,
+                ..widgets.hovered
+            },
+            active: WidgetVisuals {
+                bg_stroke: Stroke::NONE,
+                ..widgets.active
+            },
+            open: widgets.open,
+            button_padding: style.spacing.button_padding,
+            font_size: None,
+        }
+    }
+}
+
+/// How a theme's tooltips look, see [SystemTheme::tooltip].
+#[derive(Clone, Copy, Debug)]
+pub struct TooltipVisuals {
+    pub fill: Color32,
+    pub text_color: Color32,
+    pub stroke: Stroke,
+    pub rounding: Rounding,
+    pub margin: Margin,
+    pub shadow: epaint::Shadow,
+}
+
+impl TooltipVisuals {
+    /// What egui would draw a tooltip with in `style`, for the backends to change what their themes have.
+    pub(crate) fn from_style(style: &Style) -> Self {
+        Self {
+            fill: style.visuals.window_fill,
+            text_color: style.visuals.widgets.noninteractive.fg_stroke.color,
+            stroke: style.visuals.window_stroke,
+            rounding: style.visuals.menu_rounding,
+            margin: style.spacing.menu_margin,
+            shadow: style.visuals.popup_shadow,
+        }
+    }
+}
+
+/// How a theme's sliders, progress bars and spinners look, see [SystemTheme::range].
+#[derive(Clone, Copy, Debug)]
+pub struct RangeVisuals {
+    /// A slider's rail.
+    pub trough: Color32,
+    /// The part of a slider's rail before the handle.
+    pub highlight: Color32,
+    /// A slider's handle when hovered, egui draws it with the rail color otherwise.
+    pub handle: Color32,
+    /// A slider's handle while it's dragged.
+    pub handle_active: Color32,
+    /// The empty part of a progress bar.
+    pub progress_trough: Color32,
+    pub progress: Color32,
+    /// egui rounds progress bars completely unless told otherwise.
+    pub progress_rounding: Option<Rounding>,
+    pub spinner: Color32,
+}
+
+impl RangeVisuals {
+    /// What egui would draw these with in `style`, for the backends to change what their themes have.
+    pub(crate) fn from_style(style: &Style) -> Self {
+        let widgets = &style.visuals.widgets;
+        Self {
+            trough: widgets.inactive.bg_fill,
+            highlight: style.visuals.selection.bg_fill,
+            handle: widgets.hovered.bg_fill,
+            handle_active: widgets.active.bg_fill,
+            progress_trough: style.visuals.extreme_bg_color,
+            progress: style.visuals.selection.bg_fill,
+            progress_rounding: None,
+            spinner: style.visuals.strong_text_color(),
+        }
+    }
+}
+
+/// The style classes GTK themes have for widgets that should stand out. The other desktops get equivalents from their accent and negative colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThemeClass {
+    /// The button for the main action, usually in the accent color.
+    SuggestedAction,
+    /// A button for an action that deletes or breaks something.
+    DestructiveAction,
+    /// A raised box around a group of content.
+    Card,
+    Sidebar,
+    /// Content like lists and text, which is usually lighter than the window.
+    View,
+    /// Controls drawn over content, like the ones over a video.
+    Osd,
+    Error,
+    Warning,
+    Success,
+}
+
+impl ThemeClass {
+    pub const ALL: [Self; 9] = [
+        Self::SuggestedAction,
+        Self::DestructiveAction,
+        Self::Card,
+        Self::Sidebar,
+        Self::View,
+        Self::Osd,
+        Self::Error,
+        Self::Warning,
+        Self::Success,
+    ];
+
+    /// The GTK style class, without the `.`.
+    pub fn gtk_class(self) -> &'static str {
+        match self {
+            Self::SuggestedAction => "suggested-action",
+            Self::DestructiveAction => "destructive-action",
+            Self::Card => "card",
+            Self::Sidebar => "sidebar",
+            Self::View => "view",
+            Self::Osd => "osd",
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Success => "success",
+        }
+    }
+}
+
+/// How widgets with a [ThemeClass] look.
+#[derive(Clone, Copy, Debug)]
+pub struct ClassVisuals {
+    /// A button with the class.
+    pub inactive: WidgetVisuals,
+    pub hovered: WidgetVisuals,
+    pub active: WidgetVisuals,
+    /// A container with the class.
+    pub frame: Frame,
+    /// Text with the class, or on its frame.
+    pub text_color: Color32,
+}
+
+impl ClassVisuals {
+    /// egui's own buttons and group frame in `style`, for the backends to change what their themes have.
+    pub(crate) fn from_style(style: &Style) -> Self {
+        Self {
+            inactive: style.visuals.widgets.inactive,
+            hovered: style.visuals.widgets.hovered,
+            active: style.visuals.widgets.active,
+            frame: Frame::group(style),
+            text_color: style.visuals.text_color(),
+        }
+    }
+
+    /// Buttons filled with `fill`, which get closer to `text` when hovered and pressed.
+    pub(crate) fn with_button(mut self, fill: Color32, text: Color32) -> Self {
+        for (widget, amount) in [
+            (&mut self.inactive, 0.),
+            (&mut self.hovered, 0.1),
+            (&mut self.active, 0.2),
+        ] {
+            widget.weak_bg_fill = fill.mutate(text.into(), amount);
+            widget.bg_fill = widget.weak_bg_fill;
+            widget.fg_stroke = Stroke::new(1., text);
+        }
+        self
+    }
+
+    /// A frame filled with `fill`, with `text` on it.
+    pub(crate) fn with_surface(mut self, fill: Color32, text: Color32) -> Self {
+        self.frame.fill = fill;
+        self.text_color = text;
+        self
+    }
+}
+
+/// How each [ThemeClass] a theme has looks.
+pub type ThemeClasses = HashMap<ThemeClass, ClassVisuals>;
+
+/// The sets of colors desktops draw different regions of a window with.
+/// They're KDE's color sets, GTK themes have them as style classes and widgets like `.view` and `headerbar`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSet {
+    /// The window's background, which is what egui draws everything with.
+    Window,
+    /// Content like lists and text.
+    View,
+    /// The titlebar and toolbars at the top of the window.
+    Header,
+    Sidebar,
+    /// The opposite of the window colors, like dark panels in a light theme.
+    Complementary,
+}
+
+impl ColorSet {
+    pub const ALL: [Self; 5] = [
+        Self::Window,
+        Self::View,
+        Self::Header,
+        Self::Sidebar,
+        Self::Complementary,
+    ];
+
+    /// A panel frame filled with the current [SystemTheme]'s background for this set,
+    /// for what's inside to use with [UiExt::with_color_set].
+    pub fn frame(self, ui: &Ui) -> Frame {
+        let fill = SystemTheme::current(ui.ctx())
+            .and_then(|theme| {
+                theme
+                    .color_sets
+                    .get(&self)
+                    .map(|visuals| visuals.panel_fill)
+            })
+            .unwrap_or(ui.visuals().panel_fill);
+        Frame::central_panel(ui.style()).fill(fill)
+    }
+}
+
+/// `visuals` for a region with `background` behind it and `text` on it, for the backends to make their [ColorSet]s with.
+pub(crate) fn color_set_visuals(visuals: &Visuals, background: Color32, text: Color32) -> Visuals {
+    let mut visuals = visuals.clone();
+    visuals.panel_fill = background;
+    visuals.widgets.noninteractive.fg_stroke.color = text;
+    if visuals.override_text_color.is_some() {
+        visuals.override_text_color = Some(text);
+    }
+    visuals
+}
+
+/// The system theme's [Style], see [SystemTheme::load] for the rest of it.
 pub fn system_theme() -> Result<Style, Box<dyn Error>> {
-    let mut style = Style {
+    Ok(SystemTheme::load()?.style)
+}
+
+/// Like [system_theme], but for when the window isn't focused, which only some themes style differently.
+/// You can switch between the two with the egui [Context] `ctx.input(|i| i.focused)`
+pub fn system_theme_unfocused() -> Result<Style, Box<dyn Error>> {
+    Ok(SystemTheme::load_unfocused()?.style)
+}
+
+/// egui's own style for the system's dark or light mode, which the platform styles are applied on top of.
+fn base_style() -> Style {
+    Style {
         visuals: match *DARK_LIGHT_MODE {
             dark_light::Mode::Default => Visuals::default(),
             dark_light::Mode::Dark => Visuals::dark(),
             dark_light::Mode::Light => Visuals::light(),
         },
         ..Style::default()
-    };
-
-    platform::style(&mut style)?;
-
-    Ok(style)
+    }
 }
 
 /// A shortcut to create a top panel with the id specified that mimics the system titlebar on most systems. Mainly used for menubars with `menubar_style` enabled.
 #[rustfmt::skip]
 pub fn titlebar_extension<R>(ctx: &Context, id: impl Into<Id>, menubar_style: bool, add_contents: impl FnOnce(&mut Ui) -> R) -> InnerResponse<R> {
     let id = id.into();
+    let focused = ctx.input(|i| i.focused);
+    let fill = match SystemTheme::current(ctx).and_then(|theme| theme.menubar) {
+        Some(menubar) if focused => menubar.fill,
+        _ => ctx.style().visuals.titlebar(focused),
+    };
     
     TopBottomPanel::top(id).frame(
         Frame::side_top_panel(&ctx.style())
-            .fill(ctx.style().visuals.titlebar(ctx.input(|i| i.focused)))
+            .fill(fill)
             .inner_margin(Margin::same(0.))
     )
         .show(ctx, |ui| {
@@ -63,22 +531,262 @@ pub fn titlebar_extension<R>(ctx: &Context, id: impl Into<Id>, menubar_style: bo
             }
 
             if menubar_style {
-                let style = ui.style_mut();
+                style_menubar(ui);
+            }
 
-                style.visuals.widgets.active.bg_stroke = Stroke::NONE;
-                style.visuals.widgets.hovered.bg_stroke = Stroke::NONE;
-                style.visuals.widgets.inactive.weak_bg_fill = Color32::TRANSPARENT;
-                style.visuals.widgets.inactive.bg_stroke = Stroke::NONE;
+            add_contents(ui)
+        })
+}
 
-                #[cfg(target_os = "linux")] { style.spacing.button_padding = vec2(10.0, 6.0); }
-                #[cfg(not(target_os = "linux"))] { style.spacing.button_padding = vec2(7.0, 4.0); }
+/// Makes buttons in `ui` look like the system theme's flat toolbar buttons, like the ones in a headerbar, which is what [titlebar_extension] does with `menubar_style`.
+/// If the current [SystemTheme] doesn't have them, egui's buttons are just made flat.
+#[rustfmt::skip]
+pub fn style_menubar(ui: &mut Ui) {
+    if let Some(theme) = SystemTheme::current(ui.ctx()).filter(|theme| theme.menubar.is_some()) {
+        theme.style_menubar(ui.style_mut());
+        return;
+    }
 
-                // For some themes, the button background is the same as the header background
-                style.visuals.widgets.hovered.weak_bg_fill = style.visuals.widgets.hovered.weak_bg_fill.mutate(Rgba::from_gray(0.5), 0.05);
-            }
+    let style = ui.style_mut();
+    let menubar = MenubarVisuals::from_style(style);
+    style.visuals.widgets.inactive = menubar.inactive;
+    style.visuals.widgets.hovered = menubar.hovered;
+    style.visuals.widgets.active = menubar.active;
+
+    #[cfg(target_os = "linux")] { style.spacing.button_padding = vec2(10.0, 6.0); }
+    #[cfg(not(target_os = "linux"))] { style.spacing.button_padding = vec2(7.0, 4.0); }
+
+    // For some themes, the button background is the same as the header background
+    style.visuals.widgets.hovered.weak_bg_fill = style.visuals.widgets.hovered.weak_bg_fill.mutate(Rgba::from_gray(0.5), 0.05);
+}
+
+/// A shortcut for [Ui::menu_button] with the items styled like the system's menu items, see [SystemTheme::style_menu_items].
+pub fn menu_button<R>(
+    ui: &mut Ui,
+    title: impl Into<WidgetText>,
+    add_contents: impl FnOnce(&mut Ui) -> R,
+) -> InnerResponse<Option<R>> {
+    ui.menu_button(title, |ui| {
+        if let Some(theme) = SystemTheme::current(ui.ctx()) {
+            theme.style_menu_items(ui.style_mut(), false);
+        }
+        add_contents(ui)
+    })
+}
+
+/// A shortcut for [Response::context_menu] with the items styled like the system's context menu items, see [SystemTheme::style_menu_items].
+pub fn context_menu(
+    response: &Response,
+    add_contents: impl FnOnce(&mut Ui),
+) -> Option<InnerResponse<()>> {
+    response.context_menu(|ui| {
+        if let Some(theme) = SystemTheme::current(ui.ctx()) {
+            theme.style_menu_items(ui.style_mut(), true);
+        }
+        add_contents(ui)
+    })
+}
+
+/// A shortcut for [Response::on_hover_ui] with the tooltip styled like the system's tooltips, see [SystemTheme::style_tooltip].
+///
+/// egui draws tooltips with the context's style, so while a system theme with tooltips is current,
+/// this shows its own tooltip below the widget instead.
+pub fn on_hover_ui(response: Response, add_contents: impl FnOnce(&mut Ui)) -> Response {
+    let Some(theme) = SystemTheme::current(&response.ctx).filter(|theme| theme.tooltip.is_some())
+    else {
+        return response.on_hover_ui(add_contents);
+    };
+
+    let id = response.id.with("egui_system_theme::tooltip");
+    if response.enabled() && should_show_tooltip(&response, id) {
+        Area::new(id)
+            .kind(UiKind::Popup)
+            .order(Order::Tooltip)
+            .pivot(Align2::LEFT_TOP)
+            .fixed_pos(response.rect.left_bottom())
+            .default_width(theme.style.spacing.tooltip_width)
+            .interactable(false)
+            .show(&response.ctx, |ui| {
+                theme.style_tooltip(ui.style_mut());
+                ui.style_mut().interaction.selectable_labels = false;
+                Frame::popup(ui.style()).show(ui, add_contents);
+            });
+    }
+    response
+}
 
+/// Whether the tooltip `id` of `response` should show, a simpler version of what egui checks for its own.
+fn should_show_tooltip(response: &Response, id: Id) -> bool {
+    let ctx = &response.ctx;
+    if ctx.memory(|memory| memory.everything_is_visible()) {
+        return true;
+    }
+    if !response.hovered() || ctx.memory(|memory| memory.any_popup_open()) {
+        return false;
+    }
+
+    let (has_pointer, since_click, since_movement) = ctx.input(|input| {
+        (
+            input.pointer.has_pointer(),
+            input.pointer.time_since_last_click(),
+            input.pointer.time_since_last_movement(),
+        )
+    });
+    // Clicking a widget hides its tooltip until the pointer moves again
+    if !has_pointer || since_click < since_movement + 0.1 {
+        return false;
+    }
+    let was_shown = ctx.memory(|memory| {
+        memory
+            .areas()
+            .visible_last_frame(&LayerId::new(Order::Tooltip, id))
+    });
+    let delay = ctx.style().interaction.tooltip_delay;
+    if !was_shown && since_movement < delay {
+        ctx.request_repaint_after_secs(delay - since_movement);
+        return false;
+    }
+    true
+}
+
+/// A shortcut for [Response::on_hover_text] with the tooltip styled like the system's tooltips, see [on_hover_ui].
+pub fn on_hover_text(response: Response, text: impl Into<WidgetText>) -> Response {
+    on_hover_ui(response, |ui| {
+        ui.label(text);
+    })
+}
+
+/// A shortcut for adding a [Slider] styled like the system's sliders, see [SystemTheme::style_range].
+pub fn slider(ui: &mut Ui, slider: Slider) -> Response {
+    let theme = SystemTheme::current(ui.ctx());
+    ui.scope(|ui| {
+        if let Some(theme) = theme {
+            theme.style_range(ui.style_mut());
+        }
+        ui.add(slider)
+    })
+    .inner
+}
+
+/// A shortcut for adding a [ProgressBar] styled like the system's progress bars, see [SystemTheme::style_range].
+/// Its fill is replaced with [progress_fill], and its rounding with the system's if it has one, which stops it from animating.
+pub fn progress_bar(ui: &mut Ui, progress_bar: ProgressBar) -> Response {
+    let theme = SystemTheme::current(ui.ctx());
+    let mut progress_bar = progress_bar.fill(progress_fill(ui));
+    if let Some(rounding) = theme
+        .as_ref()
+        .and_then(|theme| theme.range?.progress_rounding)
+    {
+        progress_bar = progress_bar.rounding(rounding);
+    }
+    ui.scope(|ui| {
+        if let Some(theme) = theme {
+            theme.style_range(ui.style_mut());
+        }
+        ui.add(progress_bar)
+    })
+    .inner
+}
+
+/// The color the system fills progress bars with, or what egui would fill them with if there's no current [SystemTheme].
+pub fn progress_fill(ui: &Ui) -> Color32 {
+    SystemTheme::current(ui.ctx())
+        .and_then(|theme| theme.range)
+        .map_or(ui.visuals().selection.bg_fill, |range| range.progress)
+}
+
+/// A shortcut for adding a [Spinner] in the system's spinner color.
+pub fn spinner(ui: &mut Ui) -> Response {
+    let color = SystemTheme::current(ui.ctx())
+        .and_then(|theme| theme.range)
+        .map_or(ui.visuals().strong_text_color(), |range| range.spinner);
+    ui.add(Spinner::new().color(color))
+}
+
+/// The [ThemeClass]es the current [SystemTheme] has styles for, empty if there's none.
+pub fn theme_classes(ctx: &Context) -> ThemeClasses {
+    SystemTheme::current(ctx).map_or_else(ThemeClasses::new, |theme| theme.classes.clone())
+}
+
+/// Makes buttons and text in `ui` look like they have the system theme's `class`, see [SystemTheme::style_class].
+pub fn style_class(ui: &mut Ui, class: ThemeClass) {
+    if let Some(theme) = SystemTheme::current(ui.ctx()) {
+        theme.style_class(ui.style_mut(), class);
+    }
+}
+
+/// The system theme's frame for `class`, or egui's group frame if it doesn't have one.
+/// Use [style_class] inside it for text in the right color.
+pub fn class_frame(ui: &Ui, class: ThemeClass) -> Frame {
+    SystemTheme::current(ui.ctx())
+        .and_then(|theme| theme.classes.get(&class).map(|class| class.frame))
+        .unwrap_or_else(|| Frame::group(ui.style()))
+}
+
+/// Paints the system theme's focus outline around `response` if it has focus, see [SystemTheme::focus_outline].
+pub fn focus_outline(ui: &Ui, response: &Response) {
+    if let Some(theme) = SystemTheme::current(ui.ctx()) {
+        theme.paint_focus_outline(ui, response);
+    }
+}
+
+/// A shortcut for a button with the system theme's `class`, see [style_class].
+pub fn class_button(ui: &mut Ui, class: ThemeClass, text: impl Into<WidgetText>) -> Response {
+    ui.scope(|ui| {
+        style_class(ui, class);
+        let response = ui.button(text);
+        focus_outline(ui, &response);
+        response
+    })
+    .inner
+}
+
+/// A shortcut for a label with the system theme's `class`, like [ThemeClass::Error], see [style_class].
+pub fn class_label(ui: &mut Ui, class: ThemeClass, text: impl Into<WidgetText>) -> Response {
+    ui.scope(|ui| {
+        style_class(ui, class);
+        ui.label(text)
+    })
+    .inner
+}
+
+/// A button for the main action, see [ThemeClass::SuggestedAction].
+pub fn suggested_button(ui: &mut Ui, text: impl Into<WidgetText>) -> Response {
+    class_button(ui, ThemeClass::SuggestedAction, text)
+}
+
+/// A button for an action that deletes or breaks something, see [ThemeClass::DestructiveAction].
+pub fn destructive_button(ui: &mut Ui, text: impl Into<WidgetText>) -> Response {
+    class_button(ui, ThemeClass::DestructiveAction, text)
+}
+
+/// Makes widgets in `ui` look like they're drawn on the system theme's `set`, see [SystemTheme::style_color_set].
+pub fn style_color_set(ui: &mut Ui, set: ColorSet) {
+    if let Some(theme) = SystemTheme::current(ui.ctx()) {
+        theme.style_color_set(ui.style_mut(), set);
+    }
+}
+
+pub trait UiExt {
+    /// Adds the contents styled with the system theme's color `set`, see [style_color_set].
+    /// This doesn't paint the set's background, [ColorSet::frame] does.
+    fn with_color_set<R>(
+        &mut self,
+        set: ColorSet,
+        add_contents: impl FnOnce(&mut Ui) -> R,
+    ) -> InnerResponse<R>;
+}
+impl UiExt for Ui {
+    fn with_color_set<R>(
+        &mut self,
+        set: ColorSet,
+        add_contents: impl FnOnce(&mut Ui) -> R,
+    ) -> InnerResponse<R> {
+        self.scope(|ui| {
+            style_color_set(ui, set);
             add_contents(ui)
         })
+    }
 }
 
 pub trait VisualsExt {
@@ -93,7 +801,15 @@ impl VisualsExt for Visuals {
             self.code_bg_color
         }
 
-        #[cfg(not(target_os = "windows"))]
+        // On Linux noninteractive.weak_bg_fill is what disabled widgets fade towards, the headerbar color is in faint_bg_color
+        #[cfg(target_os = "linux")]
+        if focused {
+            self.faint_bg_color
+        } else {
+            self.panel_fill
+        }
+
+        #[cfg(not(any(target_os = "windows", target_os = "linux")))]
         if focused {
             self.widgets.noninteractive.weak_bg_fill
         } else {
@@ -111,3 +827,53 @@ impl Color32Ext for Color32 {
         lerp(Rgba::from(self)..=towards, amount).into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn helpers_find_the_theme_the_context_is_styled_with() {
+        let ctx = Context::default();
+        let mut focused = SystemTheme::new(Style::default());
+        focused.style.visuals.panel_fill = Color32::RED;
+        let mut unfocused = focused.clone();
+        unfocused.style.visuals.panel_fill = Color32::BLUE;
+        let hovered = unfocused.style.visuals.widgets.hovered;
+        unfocused.menu_items = Some(MenuItemVisuals {
+            hovered,
+            context_hovered: hovered,
+        });
+
+        focused.apply(&ctx);
+        unfocused.register(&ctx);
+        let current = SystemTheme::current(&ctx).unwrap();
+        assert_eq!(current.style.visuals.panel_fill, Color32::RED);
+        assert!(current.menu_items.is_none());
+
+        ctx.set_style(unfocused.style.clone());
+        assert!(SystemTheme::current(&ctx).unwrap().menu_items.is_some());
+
+        ctx.set_style(Style::default());
+        assert!(SystemTheme::current(&ctx).is_none());
+    }
+
+    #[test]
+    fn color_sets_come_from_the_current_theme() {
+        let ctx = Context::default();
+        let mut theme = SystemTheme::new(Style::default());
+        let view = color_set_visuals(&theme.style.visuals, Color32::WHITE, Color32::BLACK);
+        theme.color_sets.insert(ColorSet::View, view);
+        theme.apply(&ctx);
+
+        let _ = ctx.run(Default::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                assert_eq!(ColorSet::View.frame(ui).fill, Color32::WHITE);
+                assert_eq!(ColorSet::Header.frame(ui).fill, ui.visuals().panel_fill);
+                ui.with_color_set(ColorSet::View, |ui| {
+                    assert_eq!(ui.visuals().panel_fill, Color32::WHITE);
+                });
+            });
+        });
+    }
+}
@@ -1,23 +1,38 @@
 //! GTK parsing is so beefy that it gets it's own module.
 //! We'll have to create part of a css interpreter to parse it correctly, luckily we have lightningcss to make us an AST!
 
-use std::{collections::HashMap, error::Error, fs, path::Path};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
 
 use epaint::Shadow;
 use lightningcss::{
-    printer::PrinterOptions,
+    declaration::DeclarationBlock,
+    error::{ParserError, SelectorError},
+    media_query::{
+        MediaCondition, MediaFeature, MediaFeatureId, MediaFeatureName, MediaFeatureValue,
+        MediaList, MediaType, Operator, Qualifier, QueryFeature,
+    },
     properties::{
-        border::BorderSideWidth,
-        custom::{Function, Token, TokenOrValue, UnparsedProperty},
+        border::{BorderSideWidth, LineStyle},
+        custom::{CustomPropertyName, Function, Token, TokenList, TokenOrValue, UnparsedProperty},
+        font::{AbsoluteFontSize, FontSize, RelativeFontSize},
+        outline::OutlineStyle,
+        size::Size,
         Property, PropertyId,
     },
-    rules::{style::StyleRule, CssRule},
+    rules::CssRule,
+    selector::SelectorList,
     stylesheet::{MinifyOptions, ParserOptions, StyleSheet},
     targets::{Features, Targets},
-    traits::ToCss,
+    traits::{IntoOwned, Parse},
     values::{
-        color::{CssColor, FloatColor, LABColor, PredefinedColor, RGBA, SRGB},
-        length::{Length, LengthValue},
+        color::{CssColor, FloatColor, SystemColor, RGBA, SRGB},
+        length::LengthValue,
         percentage::DimensionPercentage,
     },
 };
@@ -25,6 +40,27 @@ use palette::{IntoColor, WithAlpha};
 
 use crate::*;
 
+mod adwaita;
+mod box_model;
+mod color;
+mod gradient;
+mod gresource;
+mod length;
+mod search;
+mod selector;
+mod settings;
+mod theme;
+mod variables;
+pub use adwaita::*;
+pub use color::*;
+pub use gresource::*;
+pub use length::*;
+pub use search::*;
+pub use selector::*;
+pub use settings::*;
+pub use theme::*;
+pub use variables::*;
+
 // NOTE: I'm nowhere near an expert on creating interpreters, so don't expect anything pretty!
 
 pub static COLOR_WORD_MAP: &[(&str, Color32)] = &[
@@ -50,7 +86,7 @@ pub static COLOR_WORD_MAP: &[(&str, Color32)] = &[
 ];
 
 macro_rules! css_values {
-    {$($name:ident($ty:ty) $as_fn:ident),* $(,)?} => {
+    {$($name:ident($ty:ty) $to_fn:ident),* $(,)?} => {
         #[derive(Debug, Clone, Copy, PartialEq)]
         pub enum CssValue {
             $($name($ty),)*
@@ -63,11 +99,11 @@ macro_rules! css_values {
         })*
 
         pub trait CssValueConversions {
-            $(fn $as_fn(self) -> Option<$ty>;)*
+            $(fn $to_fn(self) -> Option<$ty>;)*
         }
 
         impl CssValueConversions for CssValue {
-            $(fn $as_fn(self) -> Option<$ty> {
+            $(fn $to_fn(self) -> Option<$ty> {
                 match self {
                     Self::$name(value) => Some(value),
                     _ => None,
@@ -76,7 +112,7 @@ macro_rules! css_values {
         }
 
         impl CssValueConversions for Option<CssValue> {
-            $(fn $as_fn(self) -> Option<$ty> {
+            $(fn $to_fn(self) -> Option<$ty> {
                 match self? {
                     CssValue::$name(value) => Some(value),
                     _ => None,
@@ -87,13 +123,13 @@ macro_rules! css_values {
 }
 
 css_values! {
-    Color(Color32) as_color,
-    Number(f32) as_number,
+    Color(Color32) to_color,
+    Number(f32) to_number,
 }
 
 pub trait TokenOrValueIterExt {
     fn expect_token(&mut self, token: Token) -> Option<()>;
-    fn eval(&mut self, ctx: &GtkCssParseContext) -> Option<CssValue>;
+    fn eval(&mut self, style: &CascadedStyle) -> Option<CssValue>;
 }
 impl<'a, I: Iterator<Item = &'a TokenOrValue<'a>>> TokenOrValueIterExt for I {
     fn expect_token(&mut self, token: Token) -> Option<()> {
@@ -103,17 +139,25 @@ impl<'a, I: Iterator<Item = &'a TokenOrValue<'a>>> TokenOrValueIterExt for I {
         (next_token == &token).then_some(())
     }
 
-    fn eval(&mut self, ctx: &GtkCssParseContext) -> Option<CssValue> {
+    fn eval(&mut self, style: &CascadedStyle) -> Option<CssValue> {
         match self.next()? {
-            TokenOrValue::Token(Token::AtKeyword(ident)) => ctx
+            TokenOrValue::Token(Token::AtKeyword(ident)) => style
+                .ctx
                 .defined_colors
                 .get(ident.as_ref())
                 .copied()
                 .map(CssValue::from),
-            TokenOrValue::Token(Token::Ident(ident)) => COLOR_WORD_MAP
-                .iter()
-                .find(|(name, _)| ident.as_ref() == *name)
-                .map(|(_, color)| CssValue::Color(*color)),
+            TokenOrValue::Token(Token::Ident(ident)) => CssColor::parse_string(ident.as_ref())
+                .ok()
+                .map(|color| style.css_color(&color))
+                .or_else(|| {
+                    COLOR_WORD_MAP
+                        .iter()
+                        .find(|(name, _)| ident.as_ref() == *name)
+                        .map(|(_, color)| *color)
+                })
+                .map(CssValue::Color),
+            TokenOrValue::Color(color) => Some(CssValue::Color(style.css_color(color))),
             TokenOrValue::Token(Token::Number {
                 has_sign: _, value, ..
             }) => Some(CssValue::Number(*value)),
@@ -122,78 +166,219 @@ impl<'a, I: Iterator<Item = &'a TokenOrValue<'a>>> TokenOrValueIterExt for I {
                 unit_value,
                 ..
             }) => Some(CssValue::Number(*unit_value)),
-            TokenOrValue::Function(function) => ctx.eval_function(function),
+            TokenOrValue::Function(function) => style.eval_function(function),
 
             _ => None,
         }
     }
 }
 
+/// Stylesheets inside resource bundles are passed around as `resource:///...` paths, so their relative imports stay inside the bundle.
+pub const RESOURCE_SCHEME: &str = "resource://";
+
 #[derive(Debug, Clone, Default)]
 pub struct GtkCssParseContext {
+    /// The values of every `@define-color` loaded so far.
     pub defined_colors: HashMap<String, Color32>,
+    /// The expressions `@define-color` rules were written with, these get evaluated into [GtkCssParseContext::defined_colors]
+    /// after each stylesheet is loaded.
+    pub color_definitions: HashMap<String, TokenList<'static>>,
+    /// The resource bundles `resource://` imports are looked up in, usually the theme's `gtk.gresource`.
+    pub resources: Vec<GResource>,
+    /// Every style rule loaded so far, in source order.
+    pub rules: Vec<GtkCssRule>,
+    /// Whether `@media (prefers-color-scheme: dark)` rules apply, instead of the `light` ones.
+    pub dark: bool,
+    /// The contrast `@media (prefers-contrast)` rules are matched against.
+    pub contrast: GtkContrast,
+    /// The font size from `gtk-font-name` in pixels, which `rem` and the root node's `em` are relative to.
+    /// [DEFAULT_FONT_SIZE] is used if this is [None].
+    pub font_size: Option<f32>,
+    /// Whether scrollbars float over the content and only show up when it's hovered, like with `gtk-overlay-scrolling`.
+    pub overlay_scrolling: bool,
+    /// Resolves every widget in the `:backdrop` state, which GTK puts the whole window in when it isn't focused.
+    pub backdrop: bool,
+    /// Keeps translucent colors as they are, instead of painting them over whatever is behind the widget in the theme.
+    /// egui will blend them with whatever it draws them over, which isn't always what the theme had in mind.
+    pub keep_alpha: bool,
+    /// Everything that went wrong while loading, that didn't stop the rest of the theme from loading.
+    pub warnings: Vec<GtkCssWarning>,
+    /// The stylesheets being loaded right now, innermost last, so an import cycle doesn't recurse forever.
+    pub(crate) importing: Vec<PathBuf>,
+    /// What the ancestors of the nodes cascaded so far pass down, see [GtkCssParseContext::cascade].
+    pub(crate) cascade_cache: CascadeCache,
+}
+
+/// The values of `prefers-contrast`, which GTK4 gets from `gtk-interface-contrast` or the desktop's high contrast setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GtkContrast {
+    #[default]
+    NoPreference,
+    More,
+    Less,
+}
+
+impl GtkContrast {
+    /// Gets a contrast from its CSS name, like `more` or `no-preference`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "no-preference" => Self::NoPreference,
+            "more" => Self::More,
+            "less" => Self::Less,
+            _ => return None,
+        })
+    }
+}
+
+/// Relative imports can leave `..` and `.` in a resource path, which the bundle doesn't know about.
+fn normalize_resource_path(path: &str) -> String {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
 }
+
 impl GtkCssParseContext {
-    fn eval_function(&self, function: &Function) -> Option<CssValue> {
-        let mut args = function
-            .arguments
-            .0
+    /// Gets a stylesheet out of the registered resource bundles, `path` being everything after `resource://`.
+    pub fn resource_css(&self, path: &str) -> io::Result<String> {
+        let path = normalize_resource_path(path);
+
+        let resource = self
+            .resources
+            .iter()
+            .find(|resource| resource.contains(&path))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("resource {path} not found"),
+                )
+            })?;
+
+        Ok(String::from_utf8_lossy(&resource.get(&path)?).into_owned())
+    }
+
+    /// Works out every `@define-color`, now that we have all of them.
+    ///
+    /// Like GTK, a color can use ones defined after it, and the last definition of a name wins.
+    /// Colors that refer to missing colors or to themselves are left undefined.
+    /// They can also use the custom properties of the root node, which is how themes share colors between the two.
+    fn resolve_defined_colors(&mut self) {
+        let definitions = std::mem::take(&mut self.color_definitions);
+        for name in definitions.keys() {
+            self.defined_colors.remove(name);
+        }
+
+        let mut unresolved = definitions
             .iter()
-            .filter(|token| !matches!(token, TokenOrValue::Token(Token::WhiteSpace(_))));
+            .filter_map(|(name, value)| {
+                if !contains_var(value) {
+                    return Some((name, value.clone()));
+                }
+                let value = self.cascade_path(WINDOW).substitute_vars(value)?;
+                let property_id =
+                    PropertyId::Custom(CustomPropertyName::Custom("--define-color".into()));
+                match reparse(&property_id, value)? {
+                    Property::Custom(property) => Some((name, property.value)),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Keep going until a pass doesn't resolve anything new, each pass resolves at least one level of references.
+        // They're evaluated on the root node, so `currentColor` is the window's text color.
+        loop {
+            let root = self.cascade_path(WINDOW);
+            let mut resolved = Vec::new();
+            unresolved.retain(|(name, value)| {
+                let Some(color) = without_whitespace(value).eval(&root).to_color() else {
+                    return true;
+                };
+                resolved.push((name.to_string(), color));
+                false
+            });
+            if resolved.is_empty() {
+                break;
+            }
+            self.defined_colors.extend(resolved);
+            self.cascade_cache.clear();
+        }
+
+        self.color_definitions = definitions;
+    }
+}
+
+impl CascadedStyle<'_> {
+    fn eval_function(&self, function: &Function) -> Option<CssValue> {
+        let mut args = without_whitespace(&function.arguments);
 
         match function.name.as_ref() {
+            // These are all from GTK's own color expressions, see gtkcsscolorvalue.c
             "mix" => {
-                let c1 = convert_to_srgb(args.eval(self).as_color()?);
+                let c1 = args.eval(self).to_color()?;
                 args.expect_token(Token::Comma)?;
-                let c2 = convert_to_srgb(args.eval(self).as_color()?);
+                let c2 = args.eval(self).to_color()?;
                 args.expect_token(Token::Comma)?;
-                let t = args.eval(self).as_number()?;
+                let t = args.eval(self).to_number()?;
 
-                Some(CssValue::Color(convert_srgb(SRGB {
-                    r: lerp(c1.r..=c2.r, t),
-                    g: lerp(c1.g..=c2.g, t),
-                    b: lerp(c1.b..=c2.b, t),
-                    alpha: lerp(c1.alpha..=c2.alpha, t),
-                })))
+                Some(CssValue::Color(mix(c1, c2, t)))
             }
-            // We need to turn a gradient into a single color, to do this we just choose the color closest to half way
-            "linear-gradient" => {
-                let mut values = Vec::new();
-
-                args.next()?;
-                while args.expect_token(Token::Comma).is_some() {
-                    values.push(args.eval(self).as_color()?);
-                    args.eval(self).as_number()?;
-                }
+            "shade" => {
+                let color = args.eval(self).to_color()?;
+                args.expect_token(Token::Comma)?;
+                let factor = args.eval(self).to_number()?;
 
-                Some(CssValue::Color(*values.get(values.len() / 2)?))
+                Some(CssValue::Color(shade(color, factor)))
             }
-            // I found this function in the Breeze-Dark GTK3 theme, but i have no idea what it does,
-            // I couldn't find anything online about it, so i'm just assuming it's setting the alpha
+            "lighter" => Some(CssValue::Color(shade(args.eval(self).to_color()?, 1.3))),
+            "darker" => Some(CssValue::Color(shade(args.eval(self).to_color()?, 0.7))),
             "alpha" => {
-                let color = args.eval(self).as_color()?;
-                let alpha = (args.eval(self).as_number()? * 255.) as u8;
-
-                Some(CssValue::Color(Color32::from_rgba_premultiplied(
-                    color.r(),
-                    color.g(),
-                    color.b(),
-                    alpha,
-                )))
-            }
+                let color = convert_to_srgb(args.eval(self).to_color()?);
+                args.expect_token(Token::Comma)?;
+                let factor = args.eval(self).to_number()?;
 
-            _ => None,
+                Some(CssValue::Color(convert_srgb(SRGB {
+                    alpha: (color.alpha * factor).clamp(0., 1.),
+                    ..color
+                })))
+            }
+            // Gradients and images only have a single color in egui, see gradient.rs
+            _ => self.eval_image_function(function).map(CssValue::Color),
         }
     }
 
     pub fn extract_background_color(&self, property: &Property) -> Option<Color32> {
         match property {
-            Property::BackgroundColor(color) => Some(convert_css_color(color)),
+            Property::BackgroundColor(color) => Some(self.css_color(color)),
             Property::Unparsed(UnparsedProperty {
-                property_id: PropertyId::BackgroundColor | PropertyId::Background,
+                property_id: PropertyId::BackgroundColor,
                 value,
-            }) => value.0.iter().eval(self).as_color(),
-            Property::Background(background) => Some(convert_css_color(&background.first()?.color)),
+            }) => value.0.iter().eval(self).to_color(),
+            // The image is left to extract_background_image, and leaving out the color still resets it
+            Property::Unparsed(UnparsedProperty {
+                property_id: PropertyId::Background,
+                value,
+            }) => {
+                let mut tokens = without_whitespace(value).filter(|token| match token {
+                    TokenOrValue::Function(function) => {
+                        self.eval_image_function(function).is_none()
+                    }
+                    _ => true,
+                });
+                for _ in 0..value.0.len() {
+                    if let Some(color) = tokens.eval(self).to_color() {
+                        return Some(color);
+                    }
+                }
+                Some(Color32::TRANSPARENT)
+            }
+            Property::Background(background) => Some(self.css_color(&background.first()?.color)),
 
             _ => None,
         }
@@ -201,11 +386,11 @@ impl GtkCssParseContext {
 
     pub fn extract_foreground_color(&self, property: &Property) -> Option<Color32> {
         match property {
-            Property::Color(color) => Some(convert_css_color(color)),
+            Property::Color(color) => Some(self.css_color(color)),
             Property::Unparsed(UnparsedProperty {
                 property_id: PropertyId::Color,
                 value,
-            }) => value.0.iter().eval(self).as_color(),
+            }) => value.0.iter().eval(self).to_color(),
 
             _ => None,
         }
@@ -213,69 +398,110 @@ impl GtkCssParseContext {
 
     pub fn extract_border_color(&self, property: &Property) -> Option<Color32> {
         match property {
-            Property::Border(border) => Some(convert_css_color(&border.color)),
-            Property::BorderColor(color) => Some(convert_css_color(&color.top)),
+            Property::Border(border) => Some(self.css_color(&border.color)),
+            Property::BorderColor(color) => Some(self.css_color(&color.top)),
             Property::Unparsed(UnparsedProperty {
                 property_id: PropertyId::BorderColor | PropertyId::Border,
                 value,
-            }) => {
-                // Just parse until we run out of tokens or we hit a valid color
-                let mut tokens = value.0.iter();
-                for _ in 0..value.0.len() {
-                    if let Some(color) = tokens.eval(self).as_color() {
-                        return Some(color);
-                    }
-                }
-                None
-            }
+            }) => self.first_color(value),
 
             _ => None,
         }
     }
-    pub fn extract_border_width(&self, property: &Property) -> Option<f32> {
+    /// Just parse until we run out of tokens or we hit a valid color, for shorthands like `border`.
+    fn first_color(&self, value: &TokenList) -> Option<Color32> {
+        let mut tokens = value.0.iter();
+        for _ in 0..value.0.len() {
+            if let Some(color) = tokens.eval(self).to_color() {
+                return Some(color);
+            }
+        }
+        None
+    }
+    pub fn extract_outline_color(&self, property: &Property) -> Option<Color32> {
         match property {
-            Property::Border(border) => Some(convert_border_side_width(&border.width)),
-            Property::BorderWidth(width) => Some(convert_border_side_width(&width.top)),
+            Property::Outline(outline) => Some(self.css_color(&outline.color)),
+            Property::OutlineColor(color) => Some(self.css_color(color)),
             Property::Unparsed(UnparsedProperty {
-                property_id: PropertyId::BorderColor | PropertyId::Border,
+                property_id: PropertyId::OutlineColor | PropertyId::Outline,
                 value,
-            }) => value.0.iter().eval(self).as_number(),
+            }) => self.first_color(value),
 
             _ => None,
         }
     }
-    pub fn extract_border_radius(&self, property: &Property) -> Option<Rounding> {
+    pub fn extract_outline_width(&self, property: &Property) -> Option<f32> {
         match property {
-            Property::BorderRadius(radius, _) => Some(Rounding {
-                nw: convert_dimension_percentage(&radius.top_left.0),
-                ne: convert_dimension_percentage(&radius.top_right.0),
-                sw: convert_dimension_percentage(&radius.bottom_left.0),
-                se: convert_dimension_percentage(&radius.bottom_right.0),
+            Property::Outline(outline) => {
+                Some(convert_border_side_width(&outline.width, &self.lengths))
+            }
+            Property::OutlineWidth(width) => Some(convert_border_side_width(width, &self.lengths)),
+            Property::Unparsed(UnparsedProperty {
+                property_id: PropertyId::OutlineWidth | PropertyId::Outline,
+                value,
+            }) => value.0.iter().find_map(|token| match token {
+                TokenOrValue::Length(length) => Some(self.lengths.length_value(length)),
+                _ => None,
             }),
 
             _ => None,
         }
     }
-    pub fn extract_border_into<const NUM: usize>(
-        &self,
-        property: &Property,
-        dst: [(&mut Stroke, &mut Rounding); NUM],
-    ) {
-        if let Some(border_color) = self.extract_border_color(property) {
-            for (stroke, _) in dst {
-                stroke.color = border_color;
-            }
-        } else if let Some(border_width) = self.extract_border_width(property) {
-            for (stroke, _) in dst {
-                stroke.width = border_width;
+    /// Whether the outline is drawn at all, which it isn't with `outline-style: none`.
+    pub fn extract_outline_style(&self, property: &Property) -> Option<bool> {
+        let style = match property {
+            Property::Outline(outline) => &outline.style,
+            Property::OutlineStyle(style) => style,
+
+            _ => return None,
+        };
+        Some(!matches!(
+            style,
+            OutlineStyle::LineStyle(LineStyle::None | LineStyle::Hidden)
+        ))
+    }
+    pub fn extract_opacity(&self, property: &Property) -> Option<f32> {
+        match property {
+            Property::Opacity(opacity) => Some(opacity.0),
+
+            _ => None,
+        }
+    }
+    pub fn extract_border_width(&self, property: &Property) -> Option<f32> {
+        match property {
+            Property::Border(border) => {
+                Some(convert_border_side_width(&border.width, &self.lengths))
             }
-        } else if let Some(border_radius) = self.extract_border_radius(property) {
-            for (_, rounding) in dst {
-                *rounding = border_radius;
+            Property::BorderWidth(width) => {
+                Some(convert_border_side_width(&width.top, &self.lengths))
             }
+            Property::Unparsed(UnparsedProperty {
+                property_id: PropertyId::BorderWidth | PropertyId::Border,
+                value,
+            }) => value.0.iter().find_map(|token| match token {
+                TokenOrValue::Length(length) => Some(self.lengths.length_value(length)),
+                _ => None,
+            }),
+
+            _ => None,
         }
     }
+    pub fn extract_border_radius(&self, property: &Property) -> Option<Rounding> {
+        let corner = |radius: &DimensionPercentage<LengthValue>| {
+            self.lengths
+                .length_percentage(radius, self.lengths.box_size)
+        };
+        match property {
+            Property::BorderRadius(radius, _) => Some(Rounding {
+                nw: corner(&radius.top_left.0),
+                ne: corner(&radius.top_right.0),
+                sw: corner(&radius.bottom_left.0),
+                se: corner(&radius.bottom_right.0),
+            }),
 
+            _ => None,
+        }
+    }
     pub fn extract_shadow(&self, property: &Property) -> Option<Shadow> {
         match property {
             Property::BoxShadow(shadow, _) => {
@@ -286,243 +512,1226 @@ impl GtkCssParseContext {
 
                 Some(Shadow {
                     offset: vec2(
-                        convert_length(&shadow.x_offset),
-                        convert_length(&shadow.y_offset),
+                        self.lengths.length(&shadow.x_offset),
+                        self.lengths.length(&shadow.y_offset),
                     ),
-                    blur: convert_length(&shadow.blur),
-                    spread: convert_length(&shadow.spread),
-                    color: convert_css_color(&shadow.color),
+                    blur: self.lengths.length(&shadow.blur),
+                    spread: self.lengths.length(&shadow.spread),
+                    color: self.css_color(&shadow.color),
                 })
             }
 
             _ => None,
         }
     }
-}
+    /// This is used while the node's [LengthContext] is still its parent's, since `em` and percentages in `font-size`
+    /// are relative to the parent's font size.
+    pub fn extract_font_size(&self, property: &Property) -> Option<f32> {
+        let font_size = match property {
+            Property::FontSize(font_size) => font_size,
+            Property::Font(font) => &font.size,
 
-// O
+            _ => return None,
+        };
 
-#[rustfmt::skip]
-pub fn style_gtk_css(style: &mut Style, path: &Path, ctx: &mut GtkCssParseContext) -> Result<(), Box<dyn Error>> {
-    let css_content = fs::read_to_string(&path)?;
+        Some(match font_size {
+            FontSize::Length(length) => self
+                .lengths
+                .length_percentage(length, self.lengths.font_size),
+            // CSS's scale, with medium being the default font size
+            FontSize::Absolute(size) => {
+                self.lengths.root_font_size
+                    * match size {
+                        AbsoluteFontSize::XXSmall => 3. / 5.,
+                        AbsoluteFontSize::XSmall => 3. / 4.,
+                        AbsoluteFontSize::Small => 8. / 9.,
+                        AbsoluteFontSize::Medium => 1.,
+                        AbsoluteFontSize::Large => 6. / 5.,
+                        AbsoluteFontSize::XLarge => 3. / 2.,
+                        AbsoluteFontSize::XXLarge => 2.,
+                        AbsoluteFontSize::XXXLarge => 3.,
+                    }
+            }
+            FontSize::Relative(RelativeFontSize::Larger) => self.lengths.font_size * 1.2,
+            FontSize::Relative(RelativeFontSize::Smaller) => self.lengths.font_size / 1.2,
+        })
+    }
+    pub fn extract_min_height(&self, property: &Property) -> Option<f32> {
+        match property {
+            Property::MinHeight(Size::LengthPercentage(length)) => {
+                // We don't know the parent's height, so a percentage doesn't mean anything
+                Some(self.lengths.length_percentage(length, 0.))
+            }
 
-    let mut stylesheet = StyleSheet::parse(&css_content, ParserOptions {
-        filename: path.display().to_string(),
-        ..Default::default()
-    }).map_err(|err| err.to_string())?;
+            _ => None,
+        }
+    }
+}
 
-    // Try to remove some complexity from all this
-    stylesheet.minify(MinifyOptions {
-        targets: Targets {
-            include: Features::all(),
-            // exclude: Features::all(),
-            ..Default::default()
-        },
-        ..Default::default()
-    })?;
-    
-    for rule in &stylesheet.rules.0 {
-        match rule {
-            CssRule::Import(rule) => {
-                // Not sure if there's a better way to do this
-                let dir_path = path.parent().expect("Invalid css path, this is a bug!");
-                style_gtk_css(style, &dir_path.join(rule.url.to_string()), ctx)?;
-                // rule.
-                // TODO url downloading
-                // TODO actually resource:// retrieving
+impl GtkCssParseContext {
+    /// Resolves a CSS system color (`Canvas`, `ButtonText`, `Highlight`...) from the theme's named colors.
+    ///
+    /// Both libadwaita's and the older GTK3 names are tried, and egui's own colors are used if the theme doesn't have any of them.
+    pub fn system_color(&self, color: &SystemColor) -> Color32 {
+        use SystemColor::*;
+
+        let (names, fallback): (&[&str], fn(&Visuals) -> Color32) = match color {
+            Canvas | Window | Background | AppWorkspace | Scrollbar => {
+                (&["window_bg_color", "theme_bg_color"], |visuals| {
+                    visuals.window_fill
+                })
             }
-            CssRule::Style(rule) => style_gtk_rule(style, ctx, rule)?,
-            CssRule::Unknown(rule) => {
-                // Parse define-color rules
-                if rule.name != "define-color" { continue }
-                let mut prelude = rule.prelude.0.iter();
-                let Some(TokenOrValue::Token(Token::Ident(ident))) = prelude.next() else { continue };
-                let Some(TokenOrValue::Token(Token::WhiteSpace(_))) = prelude.next() else { continue };
-                match prelude.next() {
-                    Some(TokenOrValue::Color(CssColor::RGBA(rgba))) => {
-                        ctx.defined_colors.insert(ident.to_string(), convert_rgba(*rgba));
-                    }
-                    Some(TokenOrValue::Token(Token::AtKeyword(keyword))) => {
-                        let Some(color) = ctx.defined_colors.get(keyword.as_ref()) else { continue };
-                        ctx.defined_colors.insert(ident.to_string(), color.clone());
-                    }
-                    
-                    _ => {}
-                }
+            CanvasText | WindowText => {
+                (&["window_fg_color", "theme_fg_color"], Visuals::text_color)
+            }
+            ButtonFace | ThreeDFace | ButtonHighlight | ThreeDHighlight | ThreeDLightShadow => {
+                (&["window_bg_color", "theme_bg_color"], |visuals| {
+                    visuals.widgets.inactive.weak_bg_fill
+                })
+            }
+            ButtonText => (&["window_fg_color", "theme_fg_color"], |visuals| {
+                visuals.widgets.inactive.text_color()
+            }),
+            ButtonBorder | ActiveBorder | InactiveBorder | WindowFrame | ButtonShadow
+            | ThreeDShadow | ThreeDDarkShadow => (
+                &["borders", "borders_color", "unfocused_borders"],
+                |visuals| visuals.widgets.noninteractive.bg_stroke.color,
+            ),
+            Field => (&["view_bg_color", "theme_base_color"], |visuals| {
+                visuals.extreme_bg_color
+            }),
+            FieldText => (&["view_fg_color", "theme_text_color"], Visuals::text_color),
+            Highlight | SelectedItem | AccentColor => {
+                (&["accent_bg_color", "theme_selected_bg_color"], |visuals| {
+                    visuals.selection.bg_fill
+                })
+            }
+            HighlightText | SelectedItemText | AccentColorText => {
+                (&["accent_fg_color", "theme_selected_fg_color"], |visuals| {
+                    visuals.selection.stroke.color
+                })
+            }
+            GrayText => (
+                &["insensitive_fg_color", "theme_unfocused_fg_color"],
+                Visuals::weak_text_color,
+            ),
+            LinkText | ActiveText => (&["accent_color", "link_color"], |visuals| {
+                visuals.hyperlink_color
+            }),
+            VisitedText => (
+                &[
+                    "visited_link_color",
+                    "link_visited_color",
+                    "accent_color",
+                    "link_color",
+                ],
+                |visuals| visuals.hyperlink_color,
+            ),
+            Mark => (&[], |_| Color32::YELLOW),
+            MarkText => (&[], |_| Color32::BLACK),
+            Menu => (
+                &[
+                    "popover_bg_color",
+                    "menu_bg_color",
+                    "window_bg_color",
+                    "theme_bg_color",
+                ],
+                |visuals| visuals.window_fill,
+            ),
+            MenuText => (
+                &[
+                    "popover_fg_color",
+                    "menu_fg_color",
+                    "window_fg_color",
+                    "theme_fg_color",
+                ],
+                Visuals::text_color,
+            ),
+            InfoBackground => (&["tooltip_bg_color", "theme_tooltip_bg_color"], |visuals| {
+                visuals.window_fill
+            }),
+            InfoText => (
+                &["tooltip_fg_color", "theme_tooltip_fg_color"],
+                Visuals::text_color,
+            ),
+            ActiveCaption | InactiveCaption => {
+                (&["headerbar_bg_color", "theme_bg_color"], |visuals| {
+                    visuals.widgets.noninteractive.weak_bg_fill
+                })
             }
-            _ => {}
+            CaptionText | InactiveCaptionText => (
+                &["headerbar_fg_color", "theme_fg_color"],
+                Visuals::text_color,
+            ),
+        };
+
+        names
+            .iter()
+            .find_map(|name| self.defined_colors.get(*name).copied())
+            .unwrap_or_else(|| {
+                fallback(&if self.dark {
+                    Visuals::dark()
+                } else {
+                    Visuals::light()
+                })
+            })
+    }
+}
+
+/// A style rule, kept around after loading so it can be matched against widget nodes once the whole theme is loaded.
+#[derive(Debug, Clone)]
+pub struct GtkCssRule {
+    pub selectors: SelectorList<'static>,
+    pub declarations: DeclarationBlock<'static>,
+}
+
+/// Something in a theme that couldn't be loaded, like a rule lightningcss can't parse or an import that doesn't exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GtkCssWarning {
+    pub path: PathBuf,
+    /// Where in the file it went wrong, or [None] if it's about the whole file.
+    pub location: Option<GtkCssLocation>,
+    pub message: String,
+}
+
+/// A position in a stylesheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GtkCssLocation {
+    /// Starts from 1, like in editors.
+    pub line: u32,
+    /// Starts from 1, like in editors.
+    pub column: u32,
+}
+
+impl GtkCssLocation {
+    /// A location the way lightningcss reports it, with the line starting from 0.
+    fn from_lightningcss(line: u32, column: u32) -> Self {
+        Self {
+            line: line + 1,
+            column,
         }
     }
+}
 
-    Ok(())
+impl fmt::Display for GtkCssWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(location) = self.location {
+            write!(f, ":{}:{}", location.line, location.column)?;
+        }
+        write!(f, ": {}", self.message)
+    }
 }
 
-fn style_gtk_rule(
-    style: &mut Style,
-    ctx: &GtkCssParseContext,
-    rule: &StyleRule,
-) -> Result<(), Box<dyn Error>> {
-    for selectors in &rule.selectors.0 {
-        /* for selector in selectors.iter() {
-            /* if let Component::ID(id) = selector {
-                if id.to_string() == "selection" {
-                    println!("{:?}", rule.selectors);
-                }
-            } */
-            if let Component::Combinator(_) = selector {
-                println!("{:?}", rule.selectors);
-            }
-        } */
-        // let selectors = selectors.iter().collect::<Vec<_>>();
-        // TODO we should probably do this in a better way then just checking the string and a bunch of if elses
-        let selector = selectors.to_css_string(printer_options())?;
-
-        if selector == ".background" {
-            for (property, _important) in rule.declarations.iter() {
-                if let Some(bg_color) = ctx.extract_background_color(property) {
-                    style.visuals.widgets.noninteractive.bg_fill = bg_color;
-                    style.visuals.panel_fill = bg_color;
-                    style.visuals.window_fill = bg_color;
-                }
-                if let Some(fg_color) = ctx.extract_foreground_color(property) {
-                    style.visuals.widgets.noninteractive.fg_stroke = Stroke::new(1., fg_color);
-                }
+impl GtkCssParseContext {
+    /// Loads a stylesheet and everything it imports into this context.
+    ///
+    /// Only failing to read or parse the stylesheet itself is an error. Rules that don't parse and imports that fail
+    /// are skipped, with a warning added to [GtkCssParseContext::warnings].
+    pub fn load_css(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(resource_path) = path
+            .to_str()
+            .and_then(|path| path.strip_prefix(RESOURCE_SCHEME))
+        {
+            let css_content = self.resource_css(resource_path)?;
+            return self.load_css_str(&css_content, path);
+        }
+
+        self.load_css_str(&fs::read_to_string(path)?, path)
+    }
+
+    /// Like [GtkCssParseContext::load_css], but with the CSS already in memory. `path` is still used for warnings and relative imports.
+    #[rustfmt::skip]
+    pub fn load_css_str(&mut self, css_content: &str, path: &Path) -> Result<(), Box<dyn Error>> {
+        // Resource paths aren't on disk, but they're already absolute
+        let canonical_path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if self.importing.contains(&canonical_path) {
+            return Err(format!("import cycle, {} is already being loaded", path.display()).into());
+        }
+
+        let warnings = Arc::new(RwLock::new(Vec::new()));
+        let result = StyleSheet::parse(css_content, ParserOptions {
+            filename: path.display().to_string(),
+            error_recovery: true,
+            warnings: Some(warnings.clone()),
+            ..Default::default()
+        });
+        for warning in warnings.read().map(|warnings| warnings.clone()).unwrap_or_default() {
+            // lightningcss doesn't know GTK's @define-color or pseudo-classes like :backdrop, but it still hands them to us
+            match &warning.kind {
+                ParserError::AtRuleInvalid(name) if name.as_ref() == "define-color" => continue,
+                ParserError::SelectorError(SelectorError::UnsupportedPseudoClass(_)) => continue,
+                _ => {}
             }
-        } else if selector == "headerbar" {
-            for (property, _important) in rule.declarations.iter() {
-                if let Some(bg_color) = ctx.extract_background_color(property) {
-                    style.visuals.widgets.open.weak_bg_fill = bg_color;
-                    style.visuals.widgets.open.bg_fill = bg_color;
-                    style.visuals.widgets.noninteractive.weak_bg_fill = bg_color;
-                    style.visuals.faint_bg_color = bg_color;
+            let location = warning.loc.map(|loc| GtkCssLocation::from_lightningcss(loc.line, loc.column));
+            self.warn(path, location, warning.kind.to_string());
+        }
+        let mut stylesheet = result.map_err(|err| err.to_string())?;
+
+        // Minifying can rewrite media queries and merge rules across them, so only the matching blocks are left by then
+        stylesheet.rules.0 = self.flatten_media(std::mem::take(&mut stylesheet.rules.0));
+
+        // Try to remove some complexity from all this
+        if let Err(err) = stylesheet.minify(MinifyOptions {
+            targets: Targets {
+                include: Features::all(),
+                // exclude: Features::all(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }) {
+            let location = err
+                .loc
+                .map(|loc| GtkCssLocation::from_lightningcss(loc.line, loc.column));
+            self.warn(path, location, err.kind.to_string());
+        }
+
+        self.importing.push(canonical_path);
+        self.load_rules(&stylesheet.rules.0, path);
+        self.importing.pop();
+        self.cascade_cache.clear();
+
+        self.resolve_defined_colors();
+
+        Ok(())
+    }
+
+    /// Adds a warning about `path`, at `location` if it's about a specific part of it.
+    fn warn(&mut self, path: &Path, location: Option<GtkCssLocation>, message: String) {
+        self.warnings.push(GtkCssWarning {
+            path: path.to_path_buf(),
+            location,
+            message,
+        });
+    }
+
+    #[rustfmt::skip]
+    fn load_rules(&mut self, rules: &[CssRule], path: &Path) {
+        for rule in rules {
+            match rule {
+                CssRule::Import(rule) => {
+                    if !self.media_matches(&rule.media) { continue }
+                    let location = Some(GtkCssLocation::from_lightningcss(rule.loc.line, rule.loc.column));
+                    let import_path = match import_path(rule.url.as_ref(), path) {
+                        Ok(import_path) => import_path,
+                        Err(err) => {
+                            self.warn(path, location, format!("can't import {}: {err}", rule.url));
+                            continue;
+                        }
+                    };
+                    // A broken import shouldn't take the rest of the theme down with it
+                    if let Err(err) = self.load_css(&import_path) {
+                        self.warn(path, location, format!("couldn't import {}: {err}", import_path.display()));
+                    }
                 }
-                if let Some(fg_color) = ctx.extract_foreground_color(property) {
-                    style.visuals.widgets.open.fg_stroke = Stroke::new(1., fg_color);
+                // We can't tell which rules win until the whole theme is loaded, so just keep them for now
+                CssRule::Style(rule) => self.rules.push(GtkCssRule {
+                    selectors: rule.selectors.clone().into_owned(),
+                    declarations: rule.declarations.clone().into_owned(),
+                }),
+                CssRule::Unknown(rule) => {
+                    // Parse define-color rules, the value can refer to colors that haven't been defined yet so it's evaluated later
+                    if rule.name != "define-color" { continue }
+                    let mut prelude = rule.prelude.0.iter();
+                    let Some(TokenOrValue::Token(Token::Ident(ident))) = prelude.next() else { continue };
+                    let value = TokenList(prelude.cloned().collect()).into_owned();
+                    self.color_definitions.insert(ident.to_string(), value);
                 }
+                _ => {}
             }
-        } else if selector == "button" {
-            for (property, _important) in rule.declarations.iter() {
-                if let Some(bg_color) = ctx.extract_background_color(property) {
-                    style.visuals.widgets.inactive.weak_bg_fill = bg_color;
-                    style.visuals.widgets.inactive.bg_fill = bg_color;
-                    style.visuals.widgets.hovered.weak_bg_fill = bg_color;
-                    style.visuals.widgets.hovered.bg_fill = bg_color;
-                    style.visuals.widgets.active.weak_bg_fill = bg_color;
-                    style.visuals.widgets.active.bg_fill = bg_color;
+        }
+    }
+
+    /// Replaces every `@media` block with its rules if it matches, and leaves it out if it doesn't,
+    /// so the rules in a matching block are treated just like the ones outside of it.
+    fn flatten_media<'i>(&self, rules: Vec<CssRule<'i>>) -> Vec<CssRule<'i>> {
+        rules
+            .into_iter()
+            .flat_map(|rule| match rule {
+                CssRule::Media(rule) if self.media_matches(&rule.query) => {
+                    self.flatten_media(rule.rules.0)
                 }
-                if let Some(fg_color) = ctx.extract_foreground_color(property) {
-                    style.visuals.widgets.inactive.fg_stroke = Stroke::new(1., fg_color);
-                    style.visuals.widgets.hovered.fg_stroke = Stroke::new(1., fg_color);
-                    style.visuals.widgets.active.fg_stroke = Stroke::new(1., fg_color);
+                CssRule::Media(_) => Vec::new(),
+                rule => vec![rule],
+            })
+            .collect()
+    }
+
+    /// Whether a media query list matches, GTK only knows about `prefers-color-scheme` and `prefers-contrast`
+    /// so every other feature is treated as not matching.
+    pub fn media_matches(&self, media: &MediaList) -> bool {
+        // An empty list, like in a plain `@import`, always matches
+        media.media_queries.is_empty()
+            || media.media_queries.iter().any(|query| {
+                let matches = matches!(query.media_type, MediaType::All | MediaType::Screen)
+                    && query
+                        .condition
+                        .as_ref()
+                        .is_none_or(|condition| self.media_condition_matches(condition));
+                matches != (query.qualifier == Some(Qualifier::Not))
+            })
+    }
+
+    fn media_condition_matches(&self, condition: &MediaCondition) -> bool {
+        match condition {
+            MediaCondition::Feature(feature) => self.media_feature_matches(feature),
+            MediaCondition::Not(condition) => !self.media_condition_matches(condition),
+            MediaCondition::Operation {
+                operator: Operator::And,
+                conditions,
+            } => conditions
+                .iter()
+                .all(|condition| self.media_condition_matches(condition)),
+            MediaCondition::Operation {
+                operator: Operator::Or,
+                conditions,
+            } => conditions
+                .iter()
+                .any(|condition| self.media_condition_matches(condition)),
+        }
+    }
+
+    fn media_feature_matches(&self, feature: &MediaFeature) -> bool {
+        match feature {
+            QueryFeature::Plain {
+                name: MediaFeatureName::Standard(id),
+                value: MediaFeatureValue::Ident(value),
+            } => match (id, value.0.as_ref()) {
+                (MediaFeatureId::PrefersColorScheme, "dark") => self.dark,
+                (MediaFeatureId::PrefersColorScheme, "light") => !self.dark,
+                (MediaFeatureId::PrefersContrast, value) => {
+                    GtkContrast::from_name(value) == Some(self.contrast)
                 }
-                ctx.extract_border_into(
-                    property,
-                    [
-                        (
-                            &mut style.visuals.widgets.inactive.bg_stroke,
-                            &mut style.visuals.widgets.inactive.rounding,
-                        ),
-                        (
-                            &mut style.visuals.widgets.hovered.bg_stroke,
-                            &mut style.visuals.widgets.hovered.rounding,
-                        ),
-                        (
-                            &mut style.visuals.widgets.active.bg_stroke,
-                            &mut style.visuals.widgets.active.rounding,
-                        ),
-                    ],
-                );
+                _ => false,
+            },
+            QueryFeature::Boolean {
+                name: MediaFeatureName::Standard(id),
+            } => match id {
+                MediaFeatureId::PrefersColorScheme => true,
+                MediaFeatureId::PrefersContrast => self.contrast != GtkContrast::NoPreference,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Loads a GTK stylesheet that's already in memory into `ctx` and modifies a style to use everything loaded so far.
+/// `path` is still used for error messages and relative imports.
+pub fn style_gtk_css_str(
+    style: &mut Style,
+    css_content: &str,
+    path: &Path,
+    ctx: &mut GtkCssParseContext,
+) -> Result<(), Box<dyn Error>> {
+    ctx.load_css_str(css_content, path)?;
+    SystemTheme::style_with(style, |theme| {
+        style_gtk_widgets(theme, ctx);
+        Ok(())
+    })
+}
+
+/// Where an `@import` in the stylesheet at `path` points to. lightningcss gives us the same URL for `@import "a.css"`
+/// and `@import url("a.css")`, which can be relative to the importing stylesheet, a `resource://` or a `file://` URL.
+/// Any other kind of URL is an error.
+fn import_path(url: &str, path: &Path) -> Result<PathBuf, String> {
+    if let Some(resource_path) = url.strip_prefix(RESOURCE_SCHEME) {
+        return Ok(PathBuf::from(format!(
+            "{RESOURCE_SCHEME}{}",
+            normalize_resource_path(resource_path)
+        )));
+    }
+    if let Some(file_path) = url.strip_prefix("file://") {
+        // `file://localhost/path` is the same as `file:///path`
+        let file_path = file_path.strip_prefix("localhost").unwrap_or(file_path);
+        return Ok(PathBuf::from(percent_decode(file_path)));
+    }
+    if url.contains("://") {
+        // TODO url downloading
+        return Err("only files and resources can be imported".to_string());
+    }
+
+    let dir_path = path
+        .parent()
+        .ok_or_else(|| format!("{} isn't in a directory", path.display()))?;
+    let import_path = dir_path.join(url);
+    // Keep relative imports inside a resource bundle normalized too, so cycles are still caught
+    match import_path
+        .to_str()
+        .and_then(|path| path.strip_prefix(RESOURCE_SCHEME))
+    {
+        Some(resource_path) => Ok(PathBuf::from(format!(
+            "{RESOURCE_SCHEME}{}",
+            normalize_resource_path(resource_path)
+        ))),
+        None => Ok(import_path),
+    }
+}
+
+/// Decodes the `%20`s in a URL, leaving anything that isn't a valid escape as it is.
+fn percent_decode(url: &str) -> String {
+    let mut bytes = Vec::with_capacity(url.len());
+    let mut rest = url.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(escaped) => {
+                bytes.push(escaped);
+                rest = &tail[2..];
             }
-        } else if selector == "button:hover" {
-            // I'm assuming that button comes before button:hover here
-            for (property, _important) in rule.declarations.iter() {
-                if let Some(bg_color) = ctx.extract_background_color(property) {
-                    style.visuals.widgets.hovered.weak_bg_fill = bg_color;
-                    style.visuals.widgets.hovered.bg_fill = bg_color;
-                }
-                if let Some(fg_color) = ctx.extract_foreground_color(property) {
-                    style.visuals.widgets.hovered.fg_stroke = Stroke::new(1., fg_color);
-                }
-                ctx.extract_border_into(
-                    property,
-                    [(
-                        &mut style.visuals.widgets.hovered.bg_stroke,
-                        &mut style.visuals.widgets.hovered.rounding,
-                    )],
-                );
+            None => {
+                bytes.push(byte);
+                rest = tail;
             }
-        } else if selector == "button:active" {
-            for (property, _important) in rule.declarations.iter() {
-                if let Some(bg_color) = ctx.extract_background_color(property) {
-                    style.visuals.widgets.active.weak_bg_fill = bg_color;
-                    style.visuals.widgets.active.bg_fill = bg_color;
-                }
-                if let Some(fg_color) = ctx.extract_foreground_color(property) {
-                    style.visuals.widgets.active.fg_stroke = Stroke::new(1., fg_color);
-                }
-                ctx.extract_border_into(
-                    property,
-                    [
-                        (
-                            &mut style.visuals.widgets.active.bg_stroke,
-                            &mut style.visuals.widgets.active.rounding,
-                        ),
-                        (
-                            &mut style.visuals.widgets.open.bg_stroke,
-                            &mut style.visuals.widgets.open.rounding,
-                        ),
-                    ],
-                );
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// The widget nodes we resolve for each part of egui's style. They're as close to the nodes GTK4 creates as we can get without a real widget tree.
+const WINDOW: &str = "window.background.csd";
+const HEADERBAR: &str = "window.background.csd > headerbar.titlebar";
+const BUTTON: &str = "window.background.csd > button";
+/// Entries are usually in a view, like the content of a dialog, so that's the surface they're composited over.
+const ENTRY: &str = "window.background.csd > box.view > entry";
+const FRAME: &str = "window.background.csd > frame.frame";
+const LABEL: &str = "window.background.csd > label";
+/// A flat button at the start of a GTK4 and GTK3 headerbar, which menubar items are styled like.
+const HEADERBAR_BUTTONS: &[&str] = &[
+    "window.background.csd > headerbar.titlebar > windowhandle > box > box.start > button.flat",
+    "window.background.csd > headerbar.titlebar > button.flat",
+];
+/// Boxes drawn with the View, Sidebar and Complementary [ColorSet]s.
+const VIEW: &str = "window.background.csd > box.view";
+const SIDEBAR: &str = "window.background.csd > box.sidebar";
+const OSD: &str = "window.background.csd > box.osd";
+/// A selected row in a list drawn with a [ColorSet], relative to its node. Sidebars have their own kind of list.
+const SELECTED_ROW: &str = " > list > row:selected";
+const SELECTED_SIDEBAR_ROW: &str = " > list.navigation-sidebar > row:selected";
+/// Tooltips are their own window, so this is the root node.
+const TOOLTIP: &str = "tooltip.background";
+const BOX: &str = "window.background.csd > box";
+const SEPARATOR: &str = "window.background.csd > separator.horizontal";
+const CHECK_BUTTON: &str = "window.background.csd > checkbutton";
+/// The box of a check button, GTK3 calls it `checkbutton > check` too.
+const CHECK: &str = "window.background.csd > checkbutton > check";
+/// GTK4 menus are popovers, GTK3 ones are in their own popup window. Each is the menu node, then the node
+/// its contents are drawn in and one of its items, relative to it. Context menus also have `.context-menu`.
+const MENUS: &[(&str, &str, &str)] = &[
+    (
+        "window.background.csd > popover.background.menu",
+        " > contents",
+        " > contents > stack > box > modelbutton.flat",
+    ),
+    ("window.popup > menu", "", " > menuitem"),
+];
+/// A vertical scrollbar in GTK4 and GTK3, then its slider relative to it.
+const SCROLLBARS: &[(&str, &str)] = &[
+    (
+        "window.background.csd > scrolledwindow > scrollbar.vertical",
+        " > range.vertical > trough > slider",
+    ),
+    (
+        "window.background.csd > scrolledwindow > scrollbar.vertical",
+        " > contents > trough > slider",
+    ),
+];
+/// A horizontal scale's trough in GTK4 and GTK3, its `highlight` and `slider` are inside it.
+const SCALES: &[&str] = &[
+    "window.background.csd > scale.horizontal > trough",
+    "window.background.csd > scale.horizontal > contents > trough",
+];
+/// A progress bar's trough, its `progress` is inside it.
+const PROGRESS_BAR: &str = "window.background.csd > progressbar.horizontal > trough";
+const SPINNER: &str = "window.background.csd > spinner";
+/// GTK4 puts selections in the entry's text node, GTK3 right in the entry, so we try both.
+const SELECTIONS: &[&str] = &[
+    "window.background.csd > entry > text > selection",
+    "window.background.csd > entry > selection",
+    "window.background.csd > label > selection",
+];
+
+/// Modifies a theme to use the widget styles resolved from all the rules in `ctx`.
+fn style_gtk_widgets(theme: &mut SystemTheme, ctx: &GtkCssParseContext) {
+    let style = &mut theme.style;
+    let visuals = &mut style.visuals;
+
+    let window = ctx.cascade_path(WINDOW);
+    if let Some(bg_color) = window.composited_background() {
+        visuals.widgets.noninteractive.bg_fill = bg_color;
+        visuals.panel_fill = bg_color;
+        visuals.window_fill = bg_color;
+    }
+    if let Some(fg_color) = window.composited_color() {
+        visuals.widgets.noninteractive.fg_stroke = Stroke::new(1., fg_color);
+    }
+    if let Some(shadow) = window.box_shadow() {
+        visuals.window_shadow = shadow;
+    }
+    window.border_into(&mut visuals.window_stroke, &mut visuals.window_rounding);
+
+    let headerbar = ctx.cascade_path(HEADERBAR);
+    if let Some(bg_color) = headerbar.composited_background() {
+        visuals.widgets.noninteractive.weak_bg_fill = bg_color;
+        visuals.faint_bg_color = bg_color;
+    }
+
+    for (state, widget) in [
+        ("", &mut visuals.widgets.inactive),
+        (":hover", &mut visuals.widgets.hovered),
+        (":active", &mut visuals.widgets.active),
+    ] {
+        let button = ctx.cascade_path(&format!("{BUTTON}{state}"));
+        if let Some(bg_color) = button.composited_background() {
+            widget.weak_bg_fill = bg_color;
+            widget.bg_fill = bg_color;
+        }
+        if let Some(fg_color) = button.composited_color() {
+            widget.fg_stroke = Stroke::new(1., fg_color);
+        }
+        button.border_into(&mut widget.bg_stroke, &mut widget.rounding);
+
+        // egui fills check boxes and radio buttons with bg_fill, and buttons with weak_bg_fill
+        let check = ctx.cascade_path(&format!("{CHECK}{state}"));
+        if check.background_fill().is_some() {
+            if let Some(bg_color) = check.composited_background() {
+                widget.bg_fill = bg_color;
             }
-        } else if selector == "selection"
-            || selector == "label selection"
-            || selector == "entry selection"
-            || selector == "entry > text > selection"
-            || selector == "label > selection"
+        }
+    }
+    visuals.widgets.open.bg_stroke = visuals.widgets.active.bg_stroke;
+    visuals.widgets.open.rounding = visuals.widgets.active.rounding;
+
+    // egui's open widgets are the closest thing it has to toggled buttons
+    let checked = ctx.cascade_path(&format!("{BUTTON}:checked"));
+    if let Some(bg_color) = checked.composited_background() {
+        visuals.widgets.open.weak_bg_fill = bg_color;
+        visuals.widgets.open.bg_fill = bg_color;
+    }
+    if let Some(fg_color) = checked.composited_color() {
+        visuals.widgets.open.fg_stroke = Stroke::new(1., fg_color);
+    }
+    checked.border_into(
+        &mut visuals.widgets.open.bg_stroke,
+        &mut visuals.widgets.open.rounding,
+    );
+
+    // GTK4 draws its focus ring as an outline, egui draws focused widgets like pressed ones
+    theme.focus_outline = ctx
+        .cascade_path(&format!("{BUTTON}:focus:focus-visible"))
+        .outline();
+
+    // egui has no disabled colors, it fades disabled widgets halfway towards one color instead.
+    // So if the theme fades disabled text, we pick the color that fades it the same way.
+    let (label, disabled) = (
+        ctx.cascade_path(LABEL),
+        ctx.cascade_path(&format!("{LABEL}:disabled")),
+    );
+    let (normal, faded) = (label.faded_color(), disabled.faded_color());
+    if normal != faded {
+        visuals.widgets.noninteractive.weak_bg_fill = fade_target(normal, faded);
+    }
+
+    if let Some(selection) = SELECTIONS
+        .iter()
+        .map(|path| ctx.cascade_path(path))
+        .find(|selection| selection.background_fill().is_some())
+    {
+        if let Some(bg_color) = selection.composited_background() {
+            visuals.selection.bg_fill = bg_color;
+            visuals.hyperlink_color = bg_color;
+        }
+        if let Some(fg_color) = selection.composited_color() {
+            visuals.selection.stroke = Stroke::new(1., fg_color);
+        }
+    }
+
+    let entry = ctx.cascade_path(ENTRY);
+    if let Some(bg_color) = entry.composited_background() {
+        visuals.code_bg_color = bg_color;
+        visuals.extreme_bg_color = bg_color;
+    }
+
+    let frame = ctx.cascade_path(FRAME);
+    frame.border_into(
+        &mut visuals.widgets.noninteractive.bg_stroke,
+        &mut visuals.widgets.noninteractive.rounding,
+    );
+
+    // egui draws separators and the edges of panels with the same stroke as frames, so if frames don't have a border
+    // we use the separators'. Themes that don't style separators often still draw one under the headerbar,
+    // as a border on just that side.
+    let frame_border =
+        frame.border_color().is_some() || frame.border_width().is_some_and(|width| width > 0.);
+    if !frame_border {
+        if let Some(stroke) = ctx
+            .cascade_path(SEPARATOR)
+            .separator_stroke()
+            .or_else(|| headerbar.partial_border())
         {
-            for (property, _important) in rule.declarations.iter() {
-                if let Some(bg_color) = ctx.extract_background_color(property) {
-                    style.visuals.selection.bg_fill = bg_color;
-                    style.visuals.hyperlink_color = bg_color;
-                }
-                if let Some(fg_color) = ctx.extract_foreground_color(property) {
-                    style.visuals.selection.stroke = Stroke::new(1., fg_color);
-                }
+            visuals.widgets.noninteractive.bg_stroke = stroke;
+        }
+    }
+
+    // egui draws menus with the window fill and stroke, so its windows end up looking like popovers
+    if let Some(menu) = MENUS
+        .iter()
+        .map(|(menu, contents, _)| ctx.cascade_path(&format!("{menu}{contents}")))
+        .find(|menu| menu.background_fill().is_some())
+    {
+        if let Some(bg_color) = menu.composited_background() {
+            visuals.window_fill = bg_color;
+        }
+        if let Some(rounding) = menu.border_radius() {
+            visuals.menu_rounding = rounding;
+        }
+        if let Some(shadow) = menu.box_shadow() {
+            visuals.popup_shadow = shadow;
+        }
+    }
+    let hovered = visuals.widgets.hovered;
+    theme.menu_items = Some(MenuItemVisuals {
+        hovered: menu_item_hovered(ctx, hovered, "").unwrap_or(hovered),
+        context_hovered: menu_item_hovered(ctx, hovered, ".context-menu")
+            .or_else(|| menu_item_hovered(ctx, hovered, ""))
+            .unwrap_or(hovered),
+    });
+
+    style_gtk_spacing(&mut style.spacing, ctx);
+
+    // Menubars in the titlebar hover and press like the headerbar's buttons
+    let path = HEADERBAR_BUTTONS
+        .iter()
+        .find(|path| {
+            ctx.cascade_path(&format!("{path}:hover"))
+                .background_fill()
+                .is_some()
+        })
+        .unwrap_or(&HEADERBAR_BUTTONS[0]);
+    let mut menubar = crate::MenubarVisuals::from_style(style);
+    if let Some(bg_color) = ctx.cascade_path(HEADERBAR).composited_background() {
+        menubar.fill = bg_color;
+    }
+    for (state, widget) in [
+        ("", &mut menubar.inactive),
+        (":hover", &mut menubar.hovered),
+        (":active", &mut menubar.active),
+        (":checked", &mut menubar.open),
+    ] {
+        let button = ctx.cascade_path(&format!("{path}{state}"));
+        if let Some(bg_color) = button.composited_background() {
+            widget.weak_bg_fill = bg_color;
+            widget.bg_fill = bg_color;
+        }
+        if let Some(fg_color) = button.composited_color() {
+            widget.fg_stroke = Stroke::new(1., fg_color);
+        }
+        // Unlike buttons in egui, CSS boxes don't have a border unless they say so
+        widget.bg_stroke = Stroke::NONE;
+        button.border_into(&mut widget.bg_stroke, &mut widget.rounding);
+    }
+    let button = ctx.cascade_path(path);
+    if let Some(padding) = button.padding() {
+        menubar.button_padding = (padding.sum() + button.border_widths().sum()) / 2.;
+    }
+    theme.menubar = Some(menubar);
+
+    let tooltip = ctx.cascade_path(TOOLTIP);
+    let mut tooltip_visuals = crate::TooltipVisuals::from_style(style);
+    if let Some(bg_color) = tooltip.composited_background() {
+        tooltip_visuals.fill = bg_color;
+    }
+    tooltip_visuals.text_color = tooltip.faded_color();
+    // Unlike windows in egui, CSS boxes don't have a border unless they say so
+    tooltip_visuals.stroke = Stroke::NONE;
+    tooltip.border_into(&mut tooltip_visuals.stroke, &mut tooltip_visuals.rounding);
+    if let Some(padding) = tooltip.padding() {
+        tooltip_visuals.margin = padding;
+    }
+    if let Some(shadow) = tooltip.box_shadow() {
+        tooltip_visuals.shadow = shadow;
+    }
+    theme.tooltip = Some(tooltip_visuals);
+
+    let mut range = crate::RangeVisuals::from_style(style);
+    if let Some(path) = SCALES
+        .iter()
+        .find(|path| ctx.cascade_path(path).background_fill().is_some())
+    {
+        let trough = ctx.cascade_path(path);
+        if let Some(bg_color) = trough.composited_background() {
+            range.trough = bg_color;
+        }
+        if let Some(height) = trough.min_height().filter(|height| *height > 0.) {
+            style.spacing.slider_rail_height = height;
+        }
+        if let Some(bg_color) = ctx
+            .cascade_path(&format!("{path} > highlight"))
+            .composited_background()
+        {
+            range.highlight = bg_color;
+            style.visuals.slider_trailing_fill = true;
+        }
+
+        for (state, color) in [
+            (":hover", &mut range.handle),
+            (":active", &mut range.handle_active),
+        ] {
+            if let Some(bg_color) = ctx
+                .cascade_path(&format!("{path} > slider{state}"))
+                .composited_background()
+            {
+                *color = bg_color;
             }
-        } else if selector == "entry" {
-            for (property, _important) in rule.declarations.iter() {
-                if let Some(bg_color) = ctx.extract_background_color(property) {
-                    style.visuals.code_bg_color = bg_color;
-                    style.visuals.extreme_bg_color = bg_color;
+        }
+        // A slider rounded at least halfway is a circle
+        let slider = ctx.cascade_path(&format!("{path} > slider"));
+        let size = slider.min_border_box(Vec2::ZERO);
+        if size.x > 0. && size.y > 0. {
+            let radius = slider.border_radius().map_or(0., |rounding| rounding.nw);
+            style.visuals.handle_shape = if radius * 2. >= size.min_elem() {
+                style::HandleShape::Circle
+            } else {
+                style::HandleShape::Rect {
+                    aspect_ratio: size.x / size.y,
                 }
+            };
+        }
+    }
+
+    let progress_trough = ctx.cascade_path(PROGRESS_BAR);
+    if let Some(bg_color) = progress_trough.composited_background() {
+        range.progress_trough = bg_color;
+    }
+    let progress = ctx.cascade_path(&format!("{PROGRESS_BAR} > progress"));
+    if let Some(bg_color) = progress.composited_background() {
+        range.progress = bg_color;
+    }
+    range.progress_rounding = progress.border_radius();
+    range.spinner = ctx.cascade_path(SPINNER).faded_color();
+    theme.range = Some(range);
+
+    theme.classes = style_gtk_classes(style, ctx);
+    theme.color_sets = style_gtk_color_sets(&style.visuals, ctx);
+}
+
+/// Resolves the [ThemeClass]es the theme has rules for, as buttons, boxes and labels with the class.
+fn style_gtk_classes(style: &Style, ctx: &GtkCssParseContext) -> ThemeClasses {
+    let mut classes = ThemeClasses::new();
+
+    for class in ThemeClass::ALL {
+        let name = class.gtk_class();
+        // A class nothing matches would just be egui's widgets again
+        if ![BUTTON, BOX, LABEL]
+            .iter()
+            .any(|path| ctx.styles_class(path, name))
+        {
+            continue;
+        }
+        let frame = ctx.cascade_path(&format!("{BOX}.{name}"));
+        let plain_label = ctx.cascade_path(LABEL);
+        let label = ctx.cascade_path(&format!("{LABEL}.{name}"));
+
+        let mut visuals = ClassVisuals::from_style(style);
+        for (state, widget) in [
+            ("", &mut visuals.inactive),
+            (":hover", &mut visuals.hovered),
+            (":active", &mut visuals.active),
+        ] {
+            button_into(
+                &ctx.cascade_path(&format!("{BUTTON}.{name}{state}")),
+                widget,
+            );
+        }
+
+        // Unlike egui's frames, CSS boxes don't have a border or shadow unless they say so
+        visuals.frame = Frame {
+            fill: frame
+                .composited_background()
+                .unwrap_or(Color32::TRANSPARENT),
+            inner_margin: frame.padding().unwrap_or(visuals.frame.inner_margin),
+            shadow: frame.box_shadow().unwrap_or(Shadow::NONE),
+            ..Frame::none()
+        };
+        frame.border_into(&mut visuals.frame.stroke, &mut visuals.frame.rounding);
+
+        // Classes like `.error` color the label itself, the others color what's inside them
+        visuals.text_color = if label.color() != plain_label.color() {
+            label.faded_color()
+        } else {
+            ctx.cascade_path(&format!("{BOX}.{name} > label"))
+                .faded_color()
+        };
+
+        classes.insert(class, visuals);
+    }
+
+    classes
+}
+
+/// Modifies a widget to look like a button node.
+fn button_into(button: &CascadedStyle, widget: &mut WidgetVisuals) {
+    if let Some(bg_color) = button.composited_background() {
+        widget.weak_bg_fill = bg_color;
+        widget.bg_fill = bg_color;
+    }
+    if let Some(fg_color) = button.composited_color() {
+        widget.fg_stroke = Stroke::new(1., fg_color);
+    }
+    button.border_into(&mut widget.bg_stroke, &mut widget.rounding);
+}
+
+/// The visuals for each [ColorSet], from the node drawn with it and the labels and buttons inside it.
+fn style_gtk_color_sets(visuals: &Visuals, ctx: &GtkCssParseContext) -> HashMap<ColorSet, Visuals> {
+    ColorSet::ALL
+        .into_iter()
+        .map(|set| {
+            let path = match set {
+                ColorSet::Window => WINDOW,
+                ColorSet::View => VIEW,
+                ColorSet::Header => HEADERBAR,
+                ColorSet::Sidebar => SIDEBAR,
+                ColorSet::Complementary => OSD,
+            };
+            let background = ctx
+                .cascade_path(path)
+                .composited_background()
+                .unwrap_or(visuals.panel_fill);
+            let text = ctx.cascade_path(&format!("{path} > label")).faded_color();
+
+            let mut visuals = crate::color_set_visuals(visuals, background, text);
+            let row = match set {
+                ColorSet::Sidebar => SELECTED_SIDEBAR_ROW,
+                _ => SELECTED_ROW,
+            };
+            if let Some(bg_color) = ctx
+                .cascade_path(&format!("{path}{row}"))
+                .composited_background()
+            {
+                visuals.selection.bg_fill = bg_color;
             }
-        } else if selector == "window" {
-            for (property, _important) in rule.declarations.iter() {
-                if let Some(shadow) = ctx.extract_shadow(property) {
-                    style.visuals.window_shadow = shadow;
-                }
-                ctx.extract_border_into(
-                    property,
-                    [(
-                        &mut style.visuals.window_stroke,
-                        &mut style.visuals.window_rounding,
-                    )],
+            for (state, widget) in [
+                ("", &mut visuals.widgets.inactive),
+                (":hover", &mut visuals.widgets.hovered),
+                (":active", &mut visuals.widgets.active),
+            ] {
+                button_into(
+                    &ctx.cascade_path(&format!("{path} > button{state}")),
+                    widget,
                 );
             }
-        } else if selector == ".frame" {
-            for (property, _important) in rule.declarations.iter() {
-                ctx.extract_border_into(
-                    property,
-                    [(
-                        &mut style.visuals.widgets.noninteractive.bg_stroke,
-                        &mut style.visuals.widgets.noninteractive.rounding,
-                    )],
-                );
+            (set, visuals)
+        })
+        .collect()
+}
+
+/// A hovered menu item, on top of egui's hovered `widget`, or [None] if the theme doesn't give it a background.
+/// `class` is added to the menu, for context menus.
+fn menu_item_hovered(
+    ctx: &GtkCssParseContext,
+    widget: WidgetVisuals,
+    class: &str,
+) -> Option<WidgetVisuals> {
+    let item = MENUS
+        .iter()
+        .map(|(menu, _, item)| ctx.cascade_path(&format!("{menu}{class}{item}:hover")))
+        .find(|item| item.background_fill().is_some())?;
+
+    let mut widget = widget;
+    if let Some(bg_color) = item.composited_background() {
+        widget.weak_bg_fill = bg_color;
+        widget.bg_fill = bg_color;
+    }
+    if let Some(fg_color) = item.composited_color() {
+        widget.fg_stroke = Stroke::new(1., fg_color);
+    }
+    if let Some(rounding) = item.border_radius() {
+        widget.rounding = rounding;
+    }
+    Some(widget)
+}
+
+/// Modifies the spacing to match the sizes of GTK's widgets, from their padding, borders and minimum sizes.
+fn style_gtk_spacing(spacing: &mut Spacing, ctx: &GtkCssParseContext) {
+    // What egui fits in a widget without its padding, for sizes the theme leaves to the content
+    let content = spacing.interact_size - spacing.button_padding * 2.;
+
+    let button = ctx.cascade_path(BUTTON);
+    if let Some(padding) = button.padding() {
+        // egui only has one padding for both sides, and draws the border inside it
+        spacing.button_padding = (padding.sum() + button.border_widths().sum()) / 2.;
+    }
+    if button.padding().is_some() || button.min_height().is_some() {
+        spacing.interact_size = button.min_border_box(content);
+    }
+    let entry = ctx.cascade_path(ENTRY);
+    if entry.min_height().is_some() {
+        spacing.interact_size.y = spacing.interact_size.y.max(entry.min_border_box(content).y);
+    }
+
+    if let Some(padding) = MENUS
+        .iter()
+        .find_map(|(menu, contents, _)| ctx.cascade_path(&format!("{menu}{contents}")).padding())
+    {
+        spacing.menu_margin = padding;
+    }
+
+    // GTK4 boxes space out their children with `border-spacing`, otherwise it's down to the widgets' margins
+    if let Some(gap) = ctx.cascade_path(BOX).border_spacing() {
+        spacing.item_spacing = gap;
+    } else if let Some(margin) = button.margin() {
+        let gap = margin.sum();
+        if gap.x > 0. {
+            spacing.item_spacing.x = gap.x;
+        }
+        if gap.y > 0. {
+            spacing.item_spacing.y = gap.y;
+        }
+    }
+
+    let check = ctx.cascade_path(CHECK);
+    if let Some(check_size) = check.min_height() {
+        spacing.icon_width = check.min_border_box(Vec2::splat(check_size)).y;
+        // The check mark or radio dot fills the content box, unless there's nothing around it
+        if spacing.icon_width > check_size {
+            spacing.icon_width_inner = check_size;
+        }
+    }
+    if let Some(gap) = ctx.cascade_path(CHECK_BUTTON).border_spacing() {
+        spacing.icon_spacing = gap.x;
+    }
+
+    style_gtk_scroll(&mut spacing.scroll, ctx);
+}
+
+/// Modifies the scroll bars to match GTK's, which are overlay indicators that grow when hovered if the context wants overlay scrolling.
+///
+/// egui draws the handle with the widget colors, so a translucent slider (usually `alpha(currentColor, x)`) becomes
+/// the text color at that opacity, and an opaque one keeps egui's widget fill. The rounding is the widgets' too.
+fn style_gtk_scroll(scroll: &mut ScrollStyle, ctx: &GtkCssParseContext) {
+    // GTK marks overlay scrollbars with `.overlay-indicator`, and `.hovering` once the pointer is on them
+    let class = if ctx.overlay_scrolling {
+        ".overlay-indicator.hovering"
+    } else {
+        ""
+    };
+    let Some((bar, slider_path)) = SCROLLBARS.iter().find(|(bar, slider)| {
+        ctx.cascade_path(&format!("{bar}{class}{slider}"))
+            .background_fill()
+            .is_some()
+    }) else {
+        return;
+    };
+    let slider = ctx.cascade_path(&format!("{bar}{class}{slider_path}"));
+    let hovered = ctx.cascade_path(&format!("{bar}{class}{slider_path}:hover"));
+    let scrollbar = ctx.cascade_path(&format!("{bar}{class}"));
+
+    let size = slider.min_border_box(vec2(scroll.bar_width, scroll.handle_min_length));
+    let (margin, border) = (slider.margin().unwrap_or_default(), slider.border_widths());
+    // egui's bar is just the handle, so transparent borders and margins around the slider are space around the bar
+    scroll.bar_width = size.x - border.left - border.right;
+    scroll.handle_min_length = size.y - border.top - border.bottom;
+    scroll.bar_inner_margin = margin.left + border.left;
+    scroll.bar_outer_margin = margin.right + border.right;
+
+    let alpha = |color: Option<Color32>| color.map_or(0., |color| color.a() as f32 / 255.);
+    let slider_alpha = alpha(slider.background_fill());
+    let hovered_alpha = alpha(hovered.background_fill());
+    let background_alpha = alpha(scrollbar.background_fill());
+    scroll.foreground_color = slider_alpha < 1.;
+
+    if ctx.overlay_scrolling {
+        let dormant = ctx.cascade_path(&format!("{bar}.overlay-indicator{slider_path}"));
+        scroll.floating = true;
+        scroll.floating_allocated_width = 0.;
+        scroll.floating_width = dormant.min_border_box(vec2(scroll.floating_width, 0.)).x
+            - dormant.border_widths().left
+            - dormant.border_widths().right;
+
+        // GTK hides the indicator until the content is scrolled or hovered
+        scroll.dormant_handle_opacity = 0.;
+        scroll.active_handle_opacity = alpha(dormant.background_fill());
+        scroll.interact_handle_opacity = hovered_alpha;
+        scroll.dormant_background_opacity = 0.;
+        scroll.active_background_opacity = 0.;
+        scroll.interact_background_opacity = background_alpha;
+    } else if scroll.foreground_color {
+        // Solid bars are always opaque in egui, so these are floating bars that take up the same space
+        scroll.floating = true;
+        scroll.floating_width = scroll.bar_width;
+        scroll.floating_allocated_width =
+            scroll.bar_inner_margin + scroll.bar_width + scroll.bar_outer_margin;
+        scroll.dormant_handle_opacity = slider_alpha;
+        scroll.active_handle_opacity = slider_alpha;
+        scroll.interact_handle_opacity = hovered_alpha;
+        scroll.dormant_background_opacity = background_alpha;
+        scroll.active_background_opacity = background_alpha;
+        scroll.interact_background_opacity = background_alpha;
+    } else {
+        scroll.floating = false;
+    }
+}
+
+/// The color egui needs to fade `normal` towards to get `faded`, since it always fades colors halfway.
+fn fade_target(normal: Color32, faded: Color32) -> Color32 {
+    let channel = |normal: u8, faded: u8| (2 * faded as i16 - normal as i16).clamp(0, 255) as u8;
+    Color32::from_rgb(
+        channel(normal.r(), faded.r()),
+        channel(normal.g(), faded.g()),
+        channel(normal.b(), faded.b()),
+    )
+}
+
+impl CascadedStyle<'_> {
+    pub fn background_color(&self) -> Option<Color32> {
+        self.get(Self::extract_background_color)
+    }
+    pub fn background_image(&self) -> Option<Color32> {
+        self.get(Self::extract_background_image)
+    }
+    /// What the node is filled with: its `background-color`, with its `background-image` flattened and painted over it.
+    pub fn background_fill(&self) -> Option<Color32> {
+        match (self.background_color(), self.background_image()) {
+            (color, Some(image)) if image != Color32::TRANSPARENT => {
+                Some(composite(image, color.unwrap_or(Color32::TRANSPARENT)))
             }
+            (color, _) => color,
+        }
+    }
+    /// [CascadedStyle::background_fill] painted over whatever is behind the node.
+    pub fn composited_background(&self) -> Option<Color32> {
+        Some(self.composite_over(self.background_fill()?, self.parent_surface))
+    }
+    /// [CascadedStyle::color] painted over the node's own background.
+    pub fn composited_color(&self) -> Option<Color32> {
+        Some(self.composite_over(self.color()?, self.surface))
+    }
+    /// Paints a translucent `color` over `behind`, unless the context wants to keep alpha.
+    pub fn composite_over(&self, color: Color32, behind: Color32) -> Color32 {
+        if self.ctx.keep_alpha {
+            color
+        } else {
+            composite(color, behind)
+        }
+    }
+    pub fn color(&self) -> Option<Color32> {
+        self.get(Self::extract_foreground_color)
+    }
+    pub fn border_color(&self) -> Option<Color32> {
+        self.get(Self::extract_border_color)
+    }
+    pub fn border_width(&self) -> Option<f32> {
+        self.get(Self::extract_border_width)
+    }
+    pub fn border_radius(&self) -> Option<Rounding> {
+        self.get(Self::extract_border_radius)
+    }
+    pub fn box_shadow(&self) -> Option<Shadow> {
+        self.get(Self::extract_shadow)
+    }
+    pub fn min_height(&self) -> Option<f32> {
+        self.get(Self::extract_min_height)
+    }
+    pub fn opacity(&self) -> Option<f32> {
+        self.get(Self::extract_opacity)
+    }
+    /// The node's `outline` as a stroke, if it has a visible one. Like in CSS, the color defaults to `currentColor`.
+    pub fn outline(&self) -> Option<Stroke> {
+        if self.get(Self::extract_outline_style) == Some(false) {
+            return None;
         }
+        let width = self.get(Self::extract_outline_width)?;
+        let color = self
+            .get(Self::extract_outline_color)
+            .unwrap_or(self.current_color);
+
+        (width > 0. && color.a() > 0)
+            .then(|| Stroke::new(width, self.composite_over(color, self.surface)))
+    }
+    /// The node's inherited text color with its `opacity` applied, painted over its surface.
+    pub fn faded_color(&self) -> Color32 {
+        let color = convert_to_srgb(self.current_color);
+        let opacity = self.opacity().unwrap_or(1.).clamp(0., 1.);
+        self.composite_over(
+            convert_srgb(SRGB {
+                alpha: color.alpha * opacity,
+                ..color
+            }),
+            self.surface,
+        )
     }
 
-    Ok(())
+    /// Sets whichever parts of the border this node has.
+    pub fn border_into(&self, stroke: &mut Stroke, rounding: &mut Rounding) {
+        if let Some(border_color) = self.border_color() {
+            stroke.color = self.composite_over(border_color, self.surface);
+        }
+        if let Some(border_width) = self.border_width() {
+            stroke.width = border_width;
+        }
+        if let Some(border_radius) = self.border_radius() {
+            *rounding = border_radius;
+        }
+    }
+}
+
+/// The tokens of a value without the whitespace between them, which [TokenOrValueIterExt::eval] doesn't want.
+fn without_whitespace<'a>(tokens: &'a TokenList<'a>) -> impl Iterator<Item = &'a TokenOrValue<'a>> {
+    tokens
+        .0
+        .iter()
+        .filter(|token| !matches!(token, TokenOrValue::Token(Token::WhiteSpace(_))))
+}
+
+/// GTK's `mix()`, which mixes premultiplied colors so a transparent color doesn't darken the other one.
+fn mix(c1: Color32, c2: Color32, t: f32) -> Color32 {
+    let (c1, c2) = (convert_to_srgb(c1), convert_to_srgb(c2));
+    let alpha = lerp(c1.alpha..=c2.alpha, t).clamp(0., 1.);
+    if alpha == 0. {
+        return Color32::TRANSPARENT;
+    }
+    let channel = |a: f32, b: f32| (lerp(a * c1.alpha..=b * c2.alpha, t) / alpha).clamp(0., 1.);
+
+    convert_srgb(SRGB {
+        r: channel(c1.r, c2.r),
+        g: channel(c1.g, c2.g),
+        b: channel(c1.b, c2.b),
+        alpha,
+    })
+}
+
+/// Paints `top` over `bottom`, like drawing a translucent color over another one.
+/// This blends the sRGB values like GTK and browsers do, not in linear light.
+pub fn composite(top: Color32, bottom: Color32) -> Color32 {
+    let (top, bottom) = (convert_to_srgb(top), convert_to_srgb(bottom));
+    let alpha = top.alpha + bottom.alpha * (1. - top.alpha);
+    if alpha == 0. {
+        return Color32::TRANSPARENT;
+    }
+    let channel = |t: f32, b: f32| {
+        ((t * top.alpha + b * bottom.alpha * (1. - top.alpha)) / alpha).clamp(0., 1.)
+    };
+
+    convert_srgb(SRGB {
+        r: channel(top.r, bottom.r),
+        g: channel(top.g, bottom.g),
+        b: channel(top.b, bottom.b),
+        alpha,
+    })
+}
+
+/// GTK's `shade()`, which scales the lightness and saturation in HSL. `lighter()` and `darker()` are just shades of 1.3 and 0.7.
+fn shade(color: Color32, factor: f32) -> Color32 {
+    let srgb = convert_to_srgb(color);
+    let mut hsl: palette::Hsla =
+        palette::Srgba::new(srgb.r, srgb.g, srgb.b, srgb.alpha).into_color();
+    hsl.lightness = (hsl.lightness * factor).clamp(0., 1.);
+    hsl.saturation = (hsl.saturation * factor).clamp(0., 1.);
+
+    palette_convert(hsl.into_color())
 }
 
 // Conversions to interface with lightningcss
@@ -539,11 +1748,12 @@ pub fn convert_srgb(srgb: SRGB) -> Color32 {
     )
 }
 pub fn convert_to_srgb(color: Color32) -> SRGB {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
     SRGB {
-        r: color.r() as f32 / 255.,
-        g: color.g() as f32 / 255.,
-        b: color.b() as f32 / 255.,
-        alpha: color.a() as f32 / 255.,
+        r: r as f32 / 255.,
+        g: g as f32 / 255.,
+        b: b as f32 / 255.,
+        alpha: a as f32 / 255.,
     }
 }
 fn palette_convert(color: palette::Alpha<palette::Srgb, f32>) -> Color32 {
@@ -554,57 +1764,381 @@ fn palette_convert(color: palette::Alpha<palette::Srgb, f32>) -> Color32 {
         alpha: color.alpha,
     })
 }
-pub fn convert_border_side_width(width: &BorderSideWidth) -> f32 {
+/// The keywords are the same sizes GTK uses.
+pub fn convert_border_side_width(width: &BorderSideWidth, lengths: &LengthContext) -> f32 {
     match width {
-        BorderSideWidth::Thin => 0.5,
-        BorderSideWidth::Medium => 1.,
-        BorderSideWidth::Thick => 2.,
-        BorderSideWidth::Length(len) => convert_length(len),
-    }
-}
-pub fn convert_length(len: &Length) -> f32 {
-    match len {
-        Length::Value(value) => value.to_px().unwrap_or(1.),
-        Length::Calc(_) => 1., // TODO i do not want to deal with interpreting this right now
-    }
-}
-pub fn convert_dimension_percentage(value: &DimensionPercentage<LengthValue>) -> f32 {
-    match value {
-        DimensionPercentage::Dimension(value) => value.to_px().unwrap_or(1.),
-        DimensionPercentage::Percentage(p) => p.0,
-        DimensionPercentage::Calc(_) => 1.,
-    }
-}
-
-#[rustfmt::skip]
-pub fn convert_css_color(color: &CssColor) -> Color32 {
-    match color {
-        CssColor::CurrentColor => Color32::DARK_GRAY, // TODO
-        CssColor::RGBA(rgba) => convert_rgba(*rgba),
-        CssColor::LAB(lab) => match lab.as_ref().clone() {
-            LABColor::LAB(c) => convert_rgba(c.into()),
-            LABColor::LCH(c) => convert_rgba(c.into()),
-            LABColor::OKLAB(c) => convert_rgba(c.into()),
-            LABColor::OKLCH(c) => convert_rgba(c.into()),
-        }
-        CssColor::Predefined(c) => match c.as_ref() {
-            PredefinedColor::SRGB(srgb) => convert_srgb(*srgb),
-            PredefinedColor::SRGBLinear(c) => palette_convert(palette::LinSrgba::new(c.r, c.g, c.b, c.alpha).into_color()),
-            _ => Color32::DARK_GRAY,
-        },
-        CssColor::Float(c) => match c.as_ref() {
-            FloatColor::RGB(srgb) => convert_srgb(*srgb),
-            FloatColor::HSL(hsl) => palette_convert(palette::Hsl::new(hsl.h, hsl.s, hsl.l).with_alpha(hsl.alpha).into_color()),
-            FloatColor::HWB(hwb) => palette_convert(palette::Hwb::new(hwb.h, hwb.w, hwb.b).with_alpha(hwb.alpha).into_color()),
-        },
-        CssColor::LightDark(light, dark) => if *DARK_LIGHT_MODE == dark_light::Mode::Light { convert_css_color(&light) } else { convert_css_color(&dark) },
-        CssColor::System(_) => Color32::DARK_GRAY, // TODO
-    }
-}
-
-fn printer_options<'a>() -> PrinterOptions<'a> {
-    PrinterOptions {
-        minify: true,
-        ..Default::default()
+        BorderSideWidth::Thin => 1.,
+        BorderSideWidth::Medium => 3.,
+        BorderSideWidth::Thick => 5.,
+        BorderSideWidth::Length(len) => lengths.length(len),
+    }
+}
+
+impl CascadedStyle<'_> {
+    /// Converts a color, resolving `currentColor` to this node's `color` and system colors to the theme's palette.
+    #[rustfmt::skip]
+    pub fn css_color(&self, color: &CssColor) -> Color32 {
+        match color {
+            CssColor::CurrentColor => self.current_color,
+            CssColor::RGBA(rgba) => convert_rgba(*rgba),
+            CssColor::LAB(lab) => convert_lab(lab),
+            CssColor::Predefined(c) => convert_predefined(c),
+            CssColor::Float(c) => match c.as_ref() {
+                FloatColor::RGB(srgb) => convert_srgb(*srgb),
+                FloatColor::HSL(hsl) => palette_convert(palette::Hsl::new(hsl.h, hsl.s, hsl.l).with_alpha(hsl.alpha).into_color()),
+                FloatColor::HWB(hwb) => palette_convert(palette::Hwb::new(hwb.h, hwb.w, hwb.b).with_alpha(hwb.alpha).into_color()),
+            },
+            CssColor::LightDark(light, dark) => if self.ctx.dark { self.css_color(dark) } else { self.css_color(light) },
+            CssColor::System(system_color) => self.ctx.system_color(system_color),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A light context with nothing but `css` loaded.
+    pub(crate) fn load(css: &str) -> GtkCssParseContext {
+        load_into(GtkCssParseContext::default(), css)
+    }
+
+    /// Loads `css` into a context that's already set up, like with [GtkCssParseContext::dark].
+    pub(crate) fn load_into(mut ctx: GtkCssParseContext, css: &str) -> GtkCssParseContext {
+        ctx.load_css_str(css, Path::new("test.css")).unwrap();
+        ctx
+    }
+
+    const MEDIA: &str = "
+        window { color: #000000; }
+        @media (prefers-color-scheme: dark) { window { color: #ffffff; } }
+        @media (prefers-contrast: more) { window { background-color: #000000; } }
+        @media not (prefers-contrast: more) { window { background-color: #808080; } }
+        @media (prefers-color-scheme: dark) and (prefers-contrast: more) { window { border-color: #ff0000; } }
+        @media (prefers-reduced-motion: reduce), (min-width: 100px) { window { opacity: 0.5; } }";
+
+    #[test]
+    fn media_queries_match_the_context() {
+        let light = load(MEDIA);
+        let window = light.cascade_path(WINDOW);
+        assert_eq!(window.color(), Some(Color32::BLACK));
+        assert_eq!(window.background_color(), Some(Color32::from_gray(0x80)));
+        assert_eq!(window.border_color(), None);
+
+        let dark_contrast = load_into(
+            GtkCssParseContext {
+                dark: true,
+                contrast: GtkContrast::More,
+                ..Default::default()
+            },
+            MEDIA,
+        );
+        let window = dark_contrast.cascade_path(WINDOW);
+        assert_eq!(window.color(), Some(Color32::WHITE));
+        assert_eq!(window.background_color(), Some(Color32::BLACK));
+        assert_eq!(window.border_color(), Some(Color32::RED));
+    }
+
+    #[test]
+    fn media_features_gtk_does_not_have_never_match() {
+        assert_eq!(load(MEDIA).cascade_path(WINDOW).opacity(), None);
+    }
+
+    #[test]
+    fn media_rules_keep_their_source_order() {
+        let ctx = load_into(
+            GtkCssParseContext {
+                dark: true,
+                ..Default::default()
+            },
+            "@media (prefers-color-scheme: dark) { button { color: #ffffff; } }
+             button { color: #000000; }",
+        );
+        assert_eq!(ctx.cascade_path(BUTTON).color(), Some(Color32::BLACK));
+    }
+
+    const SURFACES: &str = "
+        window { background-color: #000000; }
+        .view { background-color: #ffffff; }
+        .sidebar { background-color: #0000ff; }
+        entry, .navigation-sidebar > row:selected { background-color: rgba(255, 0, 0, 0.5); }";
+
+    #[test]
+    fn translucent_colors_are_composited_over_their_parent_surface() {
+        let red = Color32::from_rgba_unmultiplied(255, 0, 0, 128);
+        let ctx = load(SURFACES);
+        assert_eq!(
+            ctx.cascade_path(ENTRY).composited_background(),
+            Some(composite(red, Color32::WHITE))
+        );
+        let row = ctx.cascade_path(&format!("{SIDEBAR}{SELECTED_SIDEBAR_ROW}"));
+        assert_eq!(
+            row.composited_background(),
+            Some(composite(red, Color32::BLUE))
+        );
+
+        let keep_alpha = load_into(
+            GtkCssParseContext {
+                keep_alpha: true,
+                ..Default::default()
+            },
+            SURFACES,
+        );
+        assert_eq!(
+            keep_alpha.cascade_path(ENTRY).composited_background(),
+            Some(red)
+        );
+    }
+
+    #[test]
+    fn broken_rules_and_imports_only_warn() {
+        let ctx = load(
+            "@import url(\"https://example.com/a.css\");
+             @import \"missing.css\";
+             button { color: #ff0000; }",
+        );
+        assert_eq!(ctx.cascade_path(BUTTON).color(), Some(Color32::RED));
+
+        let locations = ctx
+            .warnings
+            .iter()
+            .map(|warning| warning.location.map(|location| location.line))
+            .collect::<Vec<_>>();
+        assert_eq!(locations, [Some(1), Some(2)]);
+        assert!(ctx.warnings[0].message.contains("only files and resources"));
+        assert!(ctx.warnings[1].message.contains("missing.css"));
+    }
+
+    #[test]
+    fn color_expressions_evaluate_like_gtk() {
+        let ctx = load(
+            "@define-color fg #000000;
+             @define-color mixed mix(#ff0000, #0000ff, 0.25);
+             @define-color translucent_mix mix(#ff0000, transparent, 0.5);
+             @define-color faded alpha(@fg, 0.5);
+             @define-color shaded shade(#804040, 1.5);
+             @define-color lighter lighter(#804040);
+             @define-color darker darker(#804040);",
+        );
+        let color = |name: &str| ctx.defined_colors.get(name).copied();
+
+        assert_eq!(color("mixed"), Some(Color32::from_rgb(0xbf, 0, 0x3f)));
+        // Mixing with transparent only fades the color, it doesn't darken it
+        assert_eq!(
+            color("translucent_mix"),
+            Some(Color32::from_rgba_unmultiplied(0xff, 0, 0, 0x7f))
+        );
+        assert_eq!(
+            color("faded"),
+            Some(Color32::from_rgba_unmultiplied(0, 0, 0, 0x7f))
+        );
+        // Shading scales the HSL lightness and saturation, so hsl(0, 33%, 38%) becomes hsl(0, 50%, 56%)
+        assert_eq!(color("shaded"), Some(Color32::from_rgb(0xc7, 0x58, 0x58)));
+        assert_eq!(
+            color("lighter"),
+            Some(shade(Color32::from_rgb(0x80, 0x40, 0x40), 1.3))
+        );
+        assert_eq!(
+            color("darker"),
+            Some(shade(Color32::from_rgb(0x80, 0x40, 0x40), 0.7))
+        );
+    }
+
+    #[test]
+    fn define_color_resolves_forward_references_and_leaves_broken_ones_undefined() {
+        let ctx = load(
+            "@define-color selected @accent;
+             @define-color accent #ff0000;
+             @define-color accent #0000ff;
+             @define-color border mix(@selected, @missing, 0.5);
+             @define-color a @b;
+             @define-color b @a;
+             button { color: @selected; }",
+        );
+
+        assert_eq!(ctx.defined_colors.get("selected"), Some(&Color32::BLUE));
+        assert_eq!(ctx.defined_colors.get("border"), None);
+        assert_eq!(ctx.defined_colors.get("a"), None);
+        assert_eq!(ctx.defined_colors.get("b"), None);
+        assert_eq!(ctx.cascade_path(BUTTON).color(), Some(Color32::BLUE));
+    }
+
+    #[test]
+    fn disabled_widgets_fade_halfway_to_the_fade_target() {
+        let normal = Color32::from_rgb(0x20, 0x40, 0xe0);
+        let faded = Color32::from_rgb(0x60, 0x60, 0xa0);
+        let target = fade_target(normal, faded);
+        assert_eq!(target, Color32::from_rgb(0xa0, 0x80, 0x60));
+        assert_eq!(normal.lerp_to_gamma(target, 0.5), faded);
+
+        // A fade egui can't reach halfway stops at the edge of the gamut
+        assert_eq!(
+            fade_target(Color32::WHITE, Color32::from_gray(0x40)),
+            Color32::BLACK
+        );
+    }
+
+    #[test]
+    fn current_color_is_each_nodes_own_color() {
+        let ctx = load(
+            "window { color: #ff0000; border-color: currentColor; }
+             button { color: #0000ff; border-color: alpha(currentColor, 0.5); }
+             label { background-color: currentColor; }",
+        );
+
+        assert_eq!(ctx.cascade_path(WINDOW).border_color(), Some(Color32::RED));
+        assert_eq!(
+            ctx.cascade_path(BUTTON).border_color(),
+            Some(Color32::from_rgba_unmultiplied(0, 0, 255, 127))
+        );
+        // Nodes without a color of their own inherit it
+        assert_eq!(
+            ctx.cascade_path(LABEL).background_color(),
+            Some(Color32::RED)
+        );
+    }
+
+    #[test]
+    fn system_colors_use_libadwaita_then_gtk3_names() {
+        let css = "window { background-color: Canvas; color: CanvasText; }";
+        let window = |defines: &str| {
+            load(&format!("{defines}{css}"))
+                .cascade_path(WINDOW)
+                .background_color()
+        };
+
+        assert_eq!(
+            window("@define-color window_bg_color #ff0000; @define-color theme_bg_color #0000ff;"),
+            Some(Color32::RED)
+        );
+        assert_eq!(
+            window("@define-color theme_bg_color #0000ff;"),
+            Some(Color32::BLUE)
+        );
+        // Without either, it's egui's own window color
+        assert_eq!(window(""), Some(Visuals::light().window_fill));
+
+        let dark = load_into(
+            GtkCssParseContext {
+                dark: true,
+                ..Default::default()
+            },
+            css,
+        );
+        assert_eq!(
+            dark.cascade_path(WINDOW).color(),
+            Some(Visuals::dark().text_color())
+        );
+    }
+
+    #[test]
+    fn the_titlebar_is_the_headerbar_and_not_the_fade_target() {
+        let ctx = load(
+            "window { background-color: #ffffff; }
+             headerbar { background-color: #ff0000; }
+             label { color: #000000; }
+             label:disabled { opacity: 0.5; }",
+        );
+        let mut theme = SystemTheme::new(Style::default());
+        style_gtk_widgets(&mut theme, &ctx);
+        let visuals = &theme.style.visuals;
+
+        assert_eq!(visuals.titlebar(true), Color32::RED);
+        assert_ne!(visuals.widgets.noninteractive.weak_bg_fill, Color32::RED);
+        assert_eq!(theme.menubar.unwrap().fill, Color32::RED);
+    }
+
+    /// A [SystemTheme] styled with nothing but `css`.
+    fn styled(css: &str) -> SystemTheme {
+        let mut theme = SystemTheme::new(Style::default());
+        style_gtk_widgets(&mut theme, &load(css));
+        theme
+    }
+
+    #[test]
+    fn menubars_look_like_headerbar_buttons() {
+        let menubar = styled(
+            "headerbar { background-color: #ffffff; }
+             headerbar button.flat { color: #000000; padding: 4px 8px; border: 1px solid transparent; border-radius: 6px; }
+             headerbar button.flat:hover { background-color: #ff0000; }
+             headerbar button.flat:active { background-color: #00ff00; }
+             headerbar button.flat:checked { background-color: #0000ff; }",
+        )
+        .menubar
+        .unwrap();
+
+        assert_eq!(menubar.fill, Color32::WHITE);
+        // Flat buttons don't have a background until they're hovered
+        assert_eq!(menubar.inactive.weak_bg_fill, Color32::TRANSPARENT);
+        assert_eq!(menubar.hovered.weak_bg_fill, Color32::RED);
+        assert_eq!(menubar.active.weak_bg_fill, Color32::GREEN);
+        assert_eq!(menubar.open.weak_bg_fill, Color32::BLUE);
+        assert_eq!(menubar.hovered.fg_stroke.color, Color32::BLACK);
+        assert_eq!(menubar.hovered.rounding, Rounding::same(6.));
+        // The padding and border on each side
+        assert_eq!(menubar.button_padding, vec2(9., 5.));
+    }
+
+    #[test]
+    fn tooltips_are_their_own_box() {
+        let tooltip = styled(
+            "tooltip.background { background-color: rgba(0, 0, 0, 0.8); color: #ffffff; padding: 6px 10px; border-radius: 5px; }",
+        )
+        .tooltip
+        .unwrap();
+
+        assert_eq!(
+            tooltip.fill,
+            composite(
+                Color32::from_rgba_unmultiplied(0, 0, 0, 204),
+                Visuals::light().window_fill
+            )
+        );
+        assert_eq!(tooltip.text_color, Color32::WHITE);
+        // Without a border in the CSS there's no stroke, unlike egui's own tooltips
+        assert_eq!(tooltip.stroke, Stroke::NONE);
+        assert_eq!(tooltip.rounding, Rounding::same(5.));
+        assert_eq!(tooltip.margin, Margin::symmetric(10., 6.));
+    }
+
+    #[test]
+    fn ranges_are_scales_progress_bars_and_spinners() {
+        let theme = styled(
+            "scale trough { background-color: #808080; min-height: 4px; }
+             scale highlight { background-color: #0000ff; }
+             scale slider { min-width: 20px; min-height: 20px; border-radius: 10px; }
+             scale slider:hover { background-color: #ff0000; }
+             scale slider:active { background-color: #00ff00; }
+             progressbar trough { background-color: #000000; }
+             progressbar progress { background-color: #0000ff; border-radius: 3px; }
+             spinner { color: #ff0000; }",
+        );
+        let range = theme.range.unwrap();
+        let visuals = &theme.style.visuals;
+
+        assert_eq!(range.trough, Color32::from_gray(0x80));
+        assert_eq!(range.highlight, Color32::BLUE);
+        assert!(visuals.slider_trailing_fill);
+        assert_eq!(theme.style.spacing.slider_rail_height, 4.);
+        assert_eq!(range.handle, Color32::RED);
+        assert_eq!(range.handle_active, Color32::GREEN);
+        assert_eq!(visuals.handle_shape, style::HandleShape::Circle);
+        assert_eq!(range.progress_trough, Color32::BLACK);
+        assert_eq!(range.progress, Color32::BLUE);
+        assert_eq!(range.progress_rounding, Some(Rounding::same(3.)));
+        assert_eq!(range.spinner, Color32::RED);
+    }
+
+    #[test]
+    fn classes_count_only_when_a_rule_matches_through_them() {
+        let ctx = load(
+            "button, button:not(.flat) { color: #000000; }
+             .suggested-action { color: #ffffff; }
+             label.error { color: #ff0000; }",
+        );
+        assert!(!ctx.styles_class(BUTTON, "flat"));
+        assert!(ctx.styles_class(BUTTON, "suggested-action"));
+        assert!(ctx.styles_class(LABEL, "error"));
+        assert!(!ctx.styles_class(BUTTON, "error"));
     }
 }
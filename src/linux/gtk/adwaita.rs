@@ -0,0 +1,129 @@
+//! Adwaita and libadwaita's stylesheets are compiled into the libraries, so there's no CSS file on disk for us to read.
+//! Instead we bundle their palettes and the parts of their stylesheet we care about.
+
+use std::{error::Error, path::Path};
+
+use palette::{IntoColor, Oklab, Srgb};
+
+use super::{gnome_interface_setting, style_gtk_css_str, GtkCssParseContext, GtkSettings};
+use crate::*;
+
+const LIGHT_PALETTE: &str = include_str!("adwaita/light.css");
+const DARK_PALETTE: &str = include_str!("adwaita/dark.css");
+const COMMON: &str = include_str!("adwaita/common.css");
+
+/// The names GTK uses for its built-in Adwaita theme, these never have a CSS file on disk.
+pub const ADWAITA_THEME_NAMES: &[&str] = &["Adwaita", "Adwaita-dark", "Default"];
+
+/// GNOME 47's accent colors, from `org.gnome.desktop.interface accent-color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AdwaitaAccent {
+    #[default]
+    Blue,
+    Teal,
+    Green,
+    Yellow,
+    Orange,
+    Red,
+    Pink,
+    Purple,
+    Slate,
+}
+
+impl AdwaitaAccent {
+    /// The user's accent color from `gsettings`, returning [None] if it's not set or GNOME is too old to have one.
+    /// gsettings is only asked once, so this is cheap to call.
+    pub fn detect() -> Option<Self> {
+        Self::from_name(gnome_interface_setting("accent-color")?)
+    }
+
+    /// Gets an accent from its gsettings name, like `blue` or `slate`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "blue" => Self::Blue,
+            "teal" => Self::Teal,
+            "green" => Self::Green,
+            "yellow" => Self::Yellow,
+            "orange" => Self::Orange,
+            "red" => Self::Red,
+            "pink" => Self::Pink,
+            "purple" => Self::Purple,
+            "slate" => Self::Slate,
+            _ => return None,
+        })
+    }
+
+    /// The accent as `accent_color`, which unlike `accent_bg_color` is readable as text on the window background.
+    ///
+    /// libadwaita does this by clamping the lightness in oklab, so we do too.
+    pub fn standalone_color(self, dark: bool) -> Color32 {
+        let bg = Color32::from(self);
+        let mut oklab: Oklab = Srgb::new(bg.r(), bg.g(), bg.b())
+            .into_format::<f32>()
+            .into_color();
+        oklab.l = if dark {
+            oklab.l.max(0.85)
+        } else {
+            oklab.l.min(0.5)
+        };
+        let srgb: Srgb = oklab.into_color();
+        let srgb = srgb.into_format::<u8>();
+
+        Color32::from_rgb(srgb.red, srgb.green, srgb.blue)
+    }
+}
+
+/// The accent as `accent_bg_color`.
+impl From<AdwaitaAccent> for Color32 {
+    fn from(accent: AdwaitaAccent) -> Self {
+        match accent {
+            AdwaitaAccent::Blue => Color32::from_rgb(0x35, 0x84, 0xe4),
+            AdwaitaAccent::Teal => Color32::from_rgb(0x21, 0x90, 0xa4),
+            AdwaitaAccent::Green => Color32::from_rgb(0x3a, 0x94, 0x4a),
+            AdwaitaAccent::Yellow => Color32::from_rgb(0xc8, 0x88, 0x00),
+            AdwaitaAccent::Orange => Color32::from_rgb(0xed, 0x5b, 0x00),
+            AdwaitaAccent::Red => Color32::from_rgb(0xe6, 0x2d, 0x42),
+            AdwaitaAccent::Pink => Color32::from_rgb(0xd5, 0x61, 0x99),
+            AdwaitaAccent::Purple => Color32::from_rgb(0x91, 0x41, 0xac),
+            AdwaitaAccent::Slate => Color32::from_rgb(0x6f, 0x83, 0x96),
+        }
+    }
+}
+
+/// Modifies a style to look like libadwaita, with the bundled light or dark palette and the specified accent color.
+///
+/// This is what the GTK backend uses when the theme is Adwaita or Default, but you can also call it yourself
+/// if you want your app to look like a libadwaita app no matter what GTK theme is set.
+/// The font size, contrast and scrollbars still come from the user's GTK4 settings.
+pub fn style_adwaita(
+    style: &mut Style,
+    dark: bool,
+    accent: AdwaitaAccent,
+) -> Result<(), Box<dyn Error>> {
+    let settings = GtkSettings::load(4);
+    style_gtk_css_str(
+        style,
+        &adwaita_css(dark, accent),
+        Path::new("adwaita.css"),
+        &mut GtkCssParseContext {
+            dark,
+            contrast: settings.prefers_contrast(),
+            font_size: settings.font_size_px(),
+            overlay_scrolling: settings.prefers_overlay_scrolling(),
+            ..Default::default()
+        },
+    )
+}
+
+/// The whole bundled stylesheet, with the accent colors defined at the top for the rest to use.
+pub fn adwaita_css(dark: bool, accent: AdwaitaAccent) -> String {
+    let hex = |color: Color32| format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b());
+
+    format!(
+        "@define-color accent_bg_color {};\n@define-color accent_color {};\n{}\n{}",
+        hex(accent.into()),
+        hex(accent.standalone_color(dark)),
+        if dark { DARK_PALETTE } else { LIGHT_PALETTE },
+        COMMON,
+    )
+}
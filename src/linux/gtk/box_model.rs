@@ -0,0 +1,514 @@
+//! Padding, margins, minimum sizes and the separate sides of borders, for the parts of egui's `Spacing` a GTK theme decides.
+
+use egui::{vec2, Color32, Margin, Stroke, Vec2};
+use lightningcss::{
+    properties::{
+        border::{BorderSideWidth, LineStyle},
+        custom::{Token, TokenOrValue, UnparsedProperty},
+        size::Size,
+        Property, PropertyId,
+    },
+    traits::Parse,
+    values::length::LengthPercentageOrAuto,
+};
+
+use super::{convert_border_side_width, CascadedStyle};
+
+/// A side of a node's box. GTK only lays out left to right here, so `inline-start` is always the left side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+impl Side {
+    pub const ALL: [Self; 4] = [Self::Top, Self::Right, Self::Bottom, Self::Left];
+
+    /// Picks this side's value from a shorthand's sides, which CSS always lists in this order.
+    fn pick<T>(self, [top, right, bottom, left]: [T; 4]) -> T {
+        match self {
+            Self::Top => top,
+            Self::Right => right,
+            Self::Bottom => bottom,
+            Self::Left => left,
+        }
+    }
+
+    /// Picks this side's value from a logical `*-block` or `*-inline` shorthand, if it's one of the two sides it sets.
+    fn pick_logical<T>(self, block: Option<[T; 2]>, inline: Option<[T; 2]>) -> Option<T> {
+        let [start, end] = match self {
+            Self::Top | Self::Bottom => block?,
+            Self::Left | Self::Right => inline?,
+        };
+        Some(match self {
+            Self::Top | Self::Left => start,
+            Self::Bottom | Self::Right => end,
+        })
+    }
+
+    /// Whether an unparsed border shorthand sets this side.
+    fn in_border_shorthand(self, property_id: &PropertyId) -> bool {
+        match property_id {
+            PropertyId::Border => true,
+            PropertyId::BorderTop | PropertyId::BorderBlockStart => self == Self::Top,
+            PropertyId::BorderRight | PropertyId::BorderInlineEnd => self == Self::Right,
+            PropertyId::BorderBottom | PropertyId::BorderBlockEnd => self == Self::Bottom,
+            PropertyId::BorderLeft | PropertyId::BorderInlineStart => self == Self::Left,
+            PropertyId::BorderBlock => matches!(self, Self::Top | Self::Bottom),
+            PropertyId::BorderInline => matches!(self, Self::Left | Self::Right),
+            _ => false,
+        }
+    }
+}
+
+fn margin_from_sides(mut side: impl FnMut(Side) -> f32) -> Margin {
+    Margin {
+        left: side(Side::Left),
+        right: side(Side::Right),
+        top: side(Side::Top),
+        bottom: side(Side::Bottom),
+    }
+}
+
+impl CascadedStyle<'_> {
+    /// The node's `padding`, or [None] if no side of it is set.
+    pub fn padding(&self) -> Option<Margin> {
+        let sides = Side::ALL.map(|side| self.get(|style, p| style.extract_padding(p, side)));
+        sides
+            .iter()
+            .any(Option::is_some)
+            .then(|| margin_from_sides(|side| side.pick(sides).unwrap_or(0.)))
+    }
+    /// The node's `margin`, or [None] if no side of it is set.
+    pub fn margin(&self) -> Option<Margin> {
+        let sides = Side::ALL.map(|side| self.get(|style, p| style.extract_margin(p, side)));
+        sides
+            .iter()
+            .any(Option::is_some)
+            .then(|| margin_from_sides(|side| side.pick(sides).unwrap_or(0.)))
+    }
+    pub fn min_width(&self) -> Option<f32> {
+        self.get(Self::extract_min_width)
+    }
+    pub fn border_spacing(&self) -> Option<Vec2> {
+        self.get(Self::extract_border_spacing)
+    }
+
+    /// How wide each side of the border is. Like in CSS, sides with `border-style: none` (the default) don't have one.
+    pub fn border_widths(&self) -> Margin {
+        margin_from_sides(|side| self.border_side(side).map_or(0., |stroke| stroke.width))
+    }
+    /// A side of the border as a stroke, if it's drawn at all.
+    pub fn border_side(&self, side: Side) -> Option<Stroke> {
+        if !self.get(|style, p| style.extract_border_side_style(p, side))? {
+            return None;
+        }
+        let width = self.get(|style, p| style.extract_border_side_width(p, side))?;
+        let color = self
+            .get(|style, p| style.extract_border_side_color(p, side))
+            .unwrap_or(self.current_color);
+
+        (width > 0.).then(|| Stroke::new(width, self.composite_over(color, self.surface)))
+    }
+    /// The stroke of a border that's only on some sides of the node, like a headerbar's `border-bottom`.
+    /// egui can't draw those around widgets, but they work as separators.
+    pub fn partial_border(&self) -> Option<Stroke> {
+        let sides = Side::ALL.map(|side| self.border_side(side));
+        if sides.iter().all(Option::is_some) {
+            return None;
+        }
+        sides.into_iter().flatten().next()
+    }
+
+    /// GTK draws separators as a node with a background and a minimum size, so that's the stroke.
+    pub fn separator_stroke(&self) -> Option<Stroke> {
+        self.background_fill()?;
+        let width = self.min_height().unwrap_or(1.).max(1.);
+        Some(Stroke::new(width, self.composited_background()?))
+    }
+
+    /// The smallest size the node's border box can be, which is its `min-width` and `min-height` with the padding and border around them.
+    /// Sizes the theme doesn't set are taken from `content`.
+    pub fn min_border_box(&self, content: Vec2) -> Vec2 {
+        let padding = self.padding().unwrap_or_default();
+        let border = self.border_widths();
+        vec2(
+            self.min_width().unwrap_or(content.x),
+            self.min_height().unwrap_or(content.y),
+        ) + padding.sum()
+            + border.sum()
+    }
+
+    pub fn extract_padding(&self, property: &Property, side: Side) -> Option<f32> {
+        let value = match property {
+            Property::Padding(padding) => {
+                side.pick([&padding.top, &padding.right, &padding.bottom, &padding.left])
+            }
+            Property::PaddingTop(value) if side == Side::Top => value,
+            Property::PaddingRight(value) if side == Side::Right => value,
+            Property::PaddingBottom(value) if side == Side::Bottom => value,
+            Property::PaddingLeft(value) if side == Side::Left => value,
+            Property::PaddingBlockStart(value) if side == Side::Top => value,
+            Property::PaddingBlockEnd(value) if side == Side::Bottom => value,
+            Property::PaddingInlineStart(value) if side == Side::Left => value,
+            Property::PaddingInlineEnd(value) if side == Side::Right => value,
+            Property::PaddingBlock(padding) => {
+                side.pick_logical(Some([&padding.block_start, &padding.block_end]), None)?
+            }
+            Property::PaddingInline(padding) => {
+                side.pick_logical(None, Some([&padding.inline_start, &padding.inline_end]))?
+            }
+
+            _ => return None,
+        };
+        Some(self.length_or_auto(value))
+    }
+    pub fn extract_margin(&self, property: &Property, side: Side) -> Option<f32> {
+        let value = match property {
+            Property::Margin(margin) => {
+                side.pick([&margin.top, &margin.right, &margin.bottom, &margin.left])
+            }
+            Property::MarginTop(value) if side == Side::Top => value,
+            Property::MarginRight(value) if side == Side::Right => value,
+            Property::MarginBottom(value) if side == Side::Bottom => value,
+            Property::MarginLeft(value) if side == Side::Left => value,
+            Property::MarginBlockStart(value) if side == Side::Top => value,
+            Property::MarginBlockEnd(value) if side == Side::Bottom => value,
+            Property::MarginInlineStart(value) if side == Side::Left => value,
+            Property::MarginInlineEnd(value) if side == Side::Right => value,
+            Property::MarginBlock(margin) => {
+                side.pick_logical(Some([&margin.block_start, &margin.block_end]), None)?
+            }
+            Property::MarginInline(margin) => {
+                side.pick_logical(None, Some([&margin.inline_start, &margin.inline_end]))?
+            }
+
+            _ => return None,
+        };
+        Some(self.length_or_auto(value))
+    }
+    /// We don't know the size of the parent, so `auto` and percentages come out as 0.
+    fn length_or_auto(&self, value: &LengthPercentageOrAuto) -> f32 {
+        match value {
+            LengthPercentageOrAuto::Auto => 0.,
+            LengthPercentageOrAuto::LengthPercentage(length) => {
+                self.lengths.length_percentage(length, 0.)
+            }
+        }
+    }
+    pub fn extract_min_width(&self, property: &Property) -> Option<f32> {
+        match property {
+            Property::MinWidth(Size::LengthPercentage(length)) => {
+                Some(self.lengths.length_percentage(length, 0.))
+            }
+
+            _ => None,
+        }
+    }
+    /// GTK4 uses `border-spacing` for the gap between a box's children.
+    pub fn extract_border_spacing(&self, property: &Property) -> Option<Vec2> {
+        match property {
+            Property::BorderSpacing(spacing) => Some(vec2(
+                self.lengths.length(&spacing.0),
+                self.lengths.length(&spacing.1),
+            )),
+
+            _ => None,
+        }
+    }
+
+    pub fn extract_border_side_width(&self, property: &Property, side: Side) -> Option<f32> {
+        let width = match property {
+            Property::Border(border) => &border.width,
+            Property::BorderWidth(width) => {
+                side.pick([&width.top, &width.right, &width.bottom, &width.left])
+            }
+            Property::BorderTop(border) if side == Side::Top => &border.width,
+            Property::BorderRight(border) if side == Side::Right => &border.width,
+            Property::BorderBottom(border) if side == Side::Bottom => &border.width,
+            Property::BorderLeft(border) if side == Side::Left => &border.width,
+            Property::BorderTopWidth(width) if side == Side::Top => width,
+            Property::BorderRightWidth(width) if side == Side::Right => width,
+            Property::BorderBottomWidth(width) if side == Side::Bottom => width,
+            Property::BorderLeftWidth(width) if side == Side::Left => width,
+            Property::BorderBlock(border) => {
+                side.pick_logical(Some([&border.width, &border.width]), None)?
+            }
+            Property::BorderInline(border) => {
+                side.pick_logical(None, Some([&border.width, &border.width]))?
+            }
+            Property::BorderBlockWidth(width) => {
+                side.pick_logical(Some([&width.start, &width.end]), None)?
+            }
+            Property::BorderInlineWidth(width) => {
+                side.pick_logical(None, Some([&width.start, &width.end]))?
+            }
+            // A shorthand without a width resets it to `medium`
+            Property::Unparsed(UnparsedProperty { property_id, value })
+                if side.in_border_shorthand(property_id) =>
+            {
+                return Some(
+                    value
+                        .0
+                        .iter()
+                        .find_map(|token| match token {
+                            TokenOrValue::Length(length) => Some(self.lengths.length_value(length)),
+                            _ => None,
+                        })
+                        .unwrap_or_else(|| {
+                            convert_border_side_width(&BorderSideWidth::Medium, &self.lengths)
+                        }),
+                );
+            }
+
+            _ => return None,
+        };
+        Some(convert_border_side_width(width, &self.lengths))
+    }
+    /// Whether a side of the border is drawn, which it isn't with `none` or `hidden`.
+    pub fn extract_border_side_style(&self, property: &Property, side: Side) -> Option<bool> {
+        let style = match property {
+            Property::Border(border) => &border.style,
+            Property::BorderStyle(style) => {
+                side.pick([&style.top, &style.right, &style.bottom, &style.left])
+            }
+            Property::BorderTop(border) if side == Side::Top => &border.style,
+            Property::BorderRight(border) if side == Side::Right => &border.style,
+            Property::BorderBottom(border) if side == Side::Bottom => &border.style,
+            Property::BorderLeft(border) if side == Side::Left => &border.style,
+            Property::BorderTopStyle(style) if side == Side::Top => style,
+            Property::BorderRightStyle(style) if side == Side::Right => style,
+            Property::BorderBottomStyle(style) if side == Side::Bottom => style,
+            Property::BorderLeftStyle(style) if side == Side::Left => style,
+            Property::BorderBlock(border) => {
+                side.pick_logical(Some([&border.style, &border.style]), None)?
+            }
+            Property::BorderInline(border) => {
+                side.pick_logical(None, Some([&border.style, &border.style]))?
+            }
+            Property::BorderBlockStyle(style) => {
+                side.pick_logical(Some([&style.start, &style.end]), None)?
+            }
+            Property::BorderInlineStyle(style) => {
+                side.pick_logical(None, Some([&style.start, &style.end]))?
+            }
+            // A shorthand without a style resets it to `none`
+            Property::Unparsed(UnparsedProperty { property_id, value })
+                if side.in_border_shorthand(property_id) =>
+            {
+                return Some(value.0.iter().any(|token| {
+                    matches!(token, TokenOrValue::Token(Token::Ident(ident))
+                        if !matches!(ident.as_ref(), "none" | "hidden")
+                            && LineStyle::parse_string(ident.as_ref()).is_ok())
+                }));
+            }
+
+            _ => return None,
+        };
+        Some(!matches!(style, LineStyle::None | LineStyle::Hidden))
+    }
+    pub fn extract_border_side_color(&self, property: &Property, side: Side) -> Option<Color32> {
+        let color = match property {
+            Property::Border(border) => &border.color,
+            Property::BorderColor(color) => {
+                side.pick([&color.top, &color.right, &color.bottom, &color.left])
+            }
+            Property::BorderTop(border) if side == Side::Top => &border.color,
+            Property::BorderRight(border) if side == Side::Right => &border.color,
+            Property::BorderBottom(border) if side == Side::Bottom => &border.color,
+            Property::BorderLeft(border) if side == Side::Left => &border.color,
+            Property::BorderTopColor(color) if side == Side::Top => color,
+            Property::BorderRightColor(color) if side == Side::Right => color,
+            Property::BorderBottomColor(color) if side == Side::Bottom => color,
+            Property::BorderLeftColor(color) if side == Side::Left => color,
+            Property::BorderBlock(border) => {
+                side.pick_logical(Some([&border.color, &border.color]), None)?
+            }
+            Property::BorderInline(border) => {
+                side.pick_logical(None, Some([&border.color, &border.color]))?
+            }
+            Property::BorderBlockColor(color) => {
+                side.pick_logical(Some([&color.start, &color.end]), None)?
+            }
+            Property::BorderInlineColor(color) => {
+                side.pick_logical(None, Some([&color.start, &color.end]))?
+            }
+            // A shorthand without a color resets it to `currentColor`
+            Property::Unparsed(UnparsedProperty { property_id, value })
+                if side.in_border_shorthand(property_id)
+                    || *property_id == PropertyId::BorderColor
+                    || unparsed_border_color_for(property_id) == Some(side) =>
+            {
+                return Some(self.first_color(value).unwrap_or(self.current_color));
+            }
+
+            _ => return None,
+        };
+        Some(self.css_color(color))
+    }
+}
+
+/// Which side an unparsed `border-*-color` longhand is for, they're usually unparsed because they use `@define-color`s.
+fn unparsed_border_color_for(property_id: &PropertyId) -> Option<Side> {
+    Some(match property_id {
+        PropertyId::BorderTopColor | PropertyId::BorderBlockStartColor => Side::Top,
+        PropertyId::BorderRightColor | PropertyId::BorderInlineEndColor => Side::Right,
+        PropertyId::BorderBottomColor | PropertyId::BorderBlockEndColor => Side::Bottom,
+        PropertyId::BorderLeftColor | PropertyId::BorderInlineStartColor => Side::Left,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{vec2, Color32, Margin, Stroke, Style};
+
+    use super::Side;
+    use crate::{
+        linux::gtk::{style_gtk_widgets, tests::load},
+        SystemTheme,
+    };
+
+    #[test]
+    fn border_on_one_side_is_a_partial_border() {
+        let ctx = load(
+            "@define-color borders #ff0000;
+             headerbar { border-bottom: 1px solid @borders; }
+             frame { border: 1px solid @borders; }",
+        );
+        let headerbar = ctx.cascade_path("headerbar");
+
+        assert_eq!(headerbar.border_side(Side::Top), None);
+        assert_eq!(
+            headerbar.border_side(Side::Bottom),
+            Some(Stroke::new(1., Color32::RED))
+        );
+        assert_eq!(
+            headerbar.partial_border(),
+            Some(Stroke::new(1., Color32::RED))
+        );
+        // A border all the way around isn't partial
+        assert_eq!(ctx.cascade_path("frame").partial_border(), None);
+    }
+
+    #[test]
+    fn longhands_override_the_border_shorthand() {
+        let ctx = load(
+            "button { border: 2px solid; border-left-width: 0; }
+             entry { border: 2px solid; border-style: none; border-top-style: solid; }",
+        );
+
+        assert_eq!(
+            ctx.cascade_path("button").border_widths(),
+            Margin {
+                left: 0.,
+                right: 2.,
+                top: 2.,
+                bottom: 2.,
+            }
+        );
+        assert_eq!(
+            ctx.cascade_path("entry").border_widths(),
+            Margin {
+                top: 2.,
+                ..Margin::ZERO
+            }
+        );
+    }
+
+    #[test]
+    fn unparsed_shorthands_reset_the_sides_they_set() {
+        let ctx = load(
+            "window { --width: 3px; }
+             button { border-left: 1px solid; border: var(--width) solid #ff0000; }
+             entry { border: 1px solid; border-bottom: var(--width) none; }",
+        );
+
+        assert_eq!(
+            ctx.cascade_path("window > button").border_widths(),
+            Margin::same(3.)
+        );
+        assert_eq!(
+            ctx.cascade_path("window > entry").border_widths(),
+            Margin {
+                bottom: 0.,
+                ..Margin::same(1.)
+            }
+        );
+    }
+
+    #[test]
+    fn padding_shorthands_and_logical_properties() {
+        let ctx = load(
+            "button { padding: 1px 2px 3px; padding-inline: 4px 5px; }
+             label { margin: 6px; margin-block-end: 0; }",
+        );
+
+        assert_eq!(
+            ctx.cascade_path("button").padding(),
+            Some(Margin {
+                left: 4.,
+                right: 5.,
+                top: 1.,
+                bottom: 3.,
+            })
+        );
+        assert_eq!(
+            ctx.cascade_path("label").margin(),
+            Some(Margin {
+                bottom: 0.,
+                ..Margin::same(6.)
+            })
+        );
+        assert_eq!(ctx.cascade_path("box").padding(), None);
+    }
+
+    #[test]
+    fn min_border_box_adds_padding_and_borders() {
+        let ctx =
+            load("button { min-height: 24px; padding: 4px 10px; border: 1px solid #000000; }");
+
+        assert_eq!(
+            ctx.cascade_path("button").min_border_box(vec2(50., 10.)),
+            vec2(50. + 20. + 2., 24. + 8. + 2.)
+        );
+    }
+
+    #[test]
+    fn separators_are_their_background_and_min_size() {
+        let ctx = load(
+            "window { background-color: #ffffff; }
+             separator { min-height: 2px; background-color: #ff0000; }",
+        );
+
+        assert_eq!(
+            ctx.cascade_path("window > separator").separator_stroke(),
+            Some(Stroke::new(2., Color32::RED))
+        );
+        assert_eq!(ctx.cascade_path("window > box").separator_stroke(), None);
+    }
+
+    #[test]
+    fn frames_without_a_border_use_the_separator_or_headerbar_stroke() {
+        let styled = |css: &str| {
+            let mut theme = SystemTheme::new(Style::default());
+            style_gtk_widgets(&mut theme, &load(css));
+            theme.style.visuals.widgets.noninteractive.bg_stroke
+        };
+
+        assert_eq!(
+            styled("frame { border: 1px solid #ff0000; } separator { min-height: 1px; background-color: #0000ff; }"),
+            Stroke::new(1., Color32::RED)
+        );
+        assert_eq!(
+            styled("separator { min-height: 1px; background-color: #0000ff; }"),
+            Stroke::new(1., Color32::BLUE)
+        );
+        assert_eq!(
+            styled("headerbar { border-bottom: 1px solid #00ff00; }"),
+            Stroke::new(1., Color32::GREEN)
+        );
+    }
+}
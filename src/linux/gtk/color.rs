@@ -0,0 +1,245 @@
+//! CSS Color 4's color spaces, converted with palette, and mapped into sRGB the way the spec says to,
+//! so a `color(display-p3 1 0 0)` accent is a slightly duller red instead of whatever clipping the channels gives.
+
+use egui::Color32;
+use lightningcss::values::color::{LABColor, PredefinedColor, SRGB};
+use palette::{
+    chromatic_adaptation::AdaptIntoUnclamped,
+    convert::IntoColorUnclamped,
+    rgb::{AdobeRgb, DisplayP3, LinSrgb, ProPhotoRgb, Rec2020, Srgb},
+    white_point::{D50, D65},
+    Lab, Lch, Oklab, Oklch, Xyz,
+};
+
+use super::convert_srgb;
+
+/// How far off (in deltaEOK) a clipped color can be before it's noticeable.
+const JND: f32 = 0.02;
+const EPSILON: f32 = 0.0001;
+
+/// Converts a `color()` to sRGB, gamut mapping it if it doesn't fit.
+pub fn convert_predefined(color: &PredefinedColor) -> Color32 {
+    let (xyz, alpha): (Xyz<D65>, f32) = match *color {
+        PredefinedColor::SRGB(c) => (
+            Srgb::new(channel(c.r), channel(c.g), channel(c.b)).into_color_unclamped(),
+            c.alpha,
+        ),
+        PredefinedColor::SRGBLinear(c) => (
+            LinSrgb::new(channel(c.r), channel(c.g), channel(c.b)).into_color_unclamped(),
+            c.alpha,
+        ),
+        PredefinedColor::DisplayP3(c) => (
+            DisplayP3::new(channel(c.r), channel(c.g), channel(c.b)).into_color_unclamped(),
+            c.alpha,
+        ),
+        PredefinedColor::A98(c) => (
+            AdobeRgb::new(channel(c.r), channel(c.g), channel(c.b)).into_color_unclamped(),
+            c.alpha,
+        ),
+        PredefinedColor::ProPhoto(c) => {
+            let xyz: Xyz<D50> =
+                ProPhotoRgb::new(channel(c.r), channel(c.g), channel(c.b)).into_color_unclamped();
+            (xyz.adapt_into_unclamped(), c.alpha)
+        }
+        PredefinedColor::Rec2020(c) => (
+            Rec2020::new(channel(c.r), channel(c.g), channel(c.b)).into_color_unclamped(),
+            c.alpha,
+        ),
+        PredefinedColor::XYZd50(c) => (
+            Xyz::<D50>::new(channel(c.x), channel(c.y), channel(c.z)).adapt_into_unclamped(),
+            c.alpha,
+        ),
+        PredefinedColor::XYZd65(c) => (Xyz::new(channel(c.x), channel(c.y), channel(c.z)), c.alpha),
+    };
+
+    gamut_map(xyz.into_color_unclamped(), alpha)
+}
+
+/// Converts a `lab()`, `lch()`, `oklab()` or `oklch()` to sRGB, gamut mapping it if it doesn't fit.
+pub fn convert_lab(color: &LABColor) -> Color32 {
+    // lightningcss 1.0.0-alpha.61 keeps the lightness of `lab()` and `lch()` between 0 and 1, palette wants CIE's 0 to 100
+    let (oklab, alpha): (Oklab, f32) = match *color {
+        LABColor::LAB(c) => {
+            let xyz: Xyz<D50> = Lab::<D50>::new(channel(c.l) * 100., channel(c.a), channel(c.b))
+                .into_color_unclamped();
+            let xyz: Xyz<D65> = xyz.adapt_into_unclamped();
+            (xyz.into_color_unclamped(), c.alpha)
+        }
+        LABColor::LCH(c) => {
+            let xyz: Xyz<D50> = Lch::<D50>::new(channel(c.l) * 100., channel(c.c), channel(c.h))
+                .into_color_unclamped();
+            let xyz: Xyz<D65> = xyz.adapt_into_unclamped();
+            (xyz.into_color_unclamped(), c.alpha)
+        }
+        LABColor::OKLAB(c) => (
+            Oklab::new(channel(c.l), channel(c.a), channel(c.b)),
+            c.alpha,
+        ),
+        LABColor::OKLCH(c) => (
+            Oklch::new(channel(c.l), channel(c.c), channel(c.h)).into_color_unclamped(),
+            c.alpha,
+        ),
+    };
+
+    gamut_map(oklab, alpha)
+}
+
+/// Maps a color into sRGB with the CSS Color 4 algorithm: lowering the OKLCH chroma until clipping what's left
+/// is no longer noticeable, which keeps the lightness and hue.
+pub fn gamut_map(color: Oklab, alpha: f32) -> Color32 {
+    let alpha = channel(alpha).clamp(0., 1.);
+    let origin: Oklch = color.into_color_unclamped();
+
+    if origin.l >= 1. {
+        return to_color32(Srgb::new(1., 1., 1.), alpha);
+    }
+    if origin.l <= 0. {
+        return to_color32(Srgb::new(0., 0., 0.), alpha);
+    }
+    let srgb = to_srgb(origin);
+    if in_gamut(srgb) {
+        return to_color32(srgb, alpha);
+    }
+
+    let mut current = origin;
+    let mut clipped = clip(srgb);
+    if delta_eok(clipped, current) < JND {
+        return to_color32(clipped, alpha);
+    }
+
+    let (mut min, mut max) = (0., origin.chroma);
+    let mut min_in_gamut = true;
+    while max - min > EPSILON {
+        let chroma = (min + max) / 2.;
+        current.chroma = chroma;
+        let srgb = to_srgb(current);
+
+        if min_in_gamut && in_gamut(srgb) {
+            min = chroma;
+            continue;
+        }
+
+        clipped = clip(srgb);
+        let e = delta_eok(clipped, current);
+        if e < JND {
+            if JND - e < EPSILON {
+                break;
+            }
+            min_in_gamut = false;
+            min = chroma;
+        } else {
+            max = chroma;
+        }
+    }
+
+    to_color32(clipped, alpha)
+}
+
+/// `none` components come through as NaN, and count as 0.
+fn channel(value: f32) -> f32 {
+    if value.is_nan() {
+        0.
+    } else {
+        value
+    }
+}
+
+fn to_srgb(color: Oklch) -> Srgb {
+    color.into_color_unclamped()
+}
+
+fn in_gamut(color: Srgb) -> bool {
+    [color.red, color.green, color.blue]
+        .iter()
+        .all(|c| (-EPSILON..=1. + EPSILON).contains(c))
+}
+
+fn clip(color: Srgb) -> Srgb {
+    Srgb::new(
+        color.red.clamp(0., 1.),
+        color.green.clamp(0., 1.),
+        color.blue.clamp(0., 1.),
+    )
+}
+
+/// The distance between two colors in Oklab.
+fn delta_eok(a: Srgb, b: Oklch) -> f32 {
+    let a: Oklab = a.into_color_unclamped();
+    let b: Oklab = b.into_color_unclamped();
+    ((a.l - b.l).powi(2) + (a.a - b.a).powi(2) + (a.b - b.b).powi(2)).sqrt()
+}
+
+fn to_color32(color: Srgb, alpha: f32) -> Color32 {
+    let color = clip(color);
+    convert_srgb(SRGB {
+        r: color.red,
+        g: color.green,
+        b: color.blue,
+        alpha,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Color32;
+    use palette::{convert::IntoColorUnclamped, rgb::DisplayP3, Oklab, Oklch, Srgb};
+
+    use super::gamut_map;
+    use crate::linux::gtk::tests::load;
+
+    fn oklch(color: Color32) -> Oklch {
+        let srgb = Srgb::new(color.r(), color.g(), color.b()).into_format::<f32>();
+        srgb.into_color_unclamped()
+    }
+
+    #[test]
+    fn colors_in_srgb_stay_the_same() {
+        let ctx = load(
+            "button { color: color(srgb 1 0 0); background-color: oklab(62.79554% 0.22486 0.12585); }",
+        );
+        let button = ctx.cascade_path("button");
+
+        assert_eq!(button.color(), Some(Color32::RED));
+        assert_eq!(button.background_color(), Some(Color32::RED));
+    }
+
+    #[test]
+    fn out_of_gamut_colors_keep_their_lightness_and_hue() {
+        let p3_red: Oklab = DisplayP3::new(1., 0., 0.).into_color_unclamped();
+        let origin: Oklch = p3_red.into_color_unclamped();
+        let mapped = gamut_map(p3_red, 1.);
+        let result = oklch(mapped);
+
+        // Clipping the channels would just give sRGB's red, which is a different hue and lightness
+        assert_ne!(mapped, Color32::RED);
+        assert!((result.l - origin.l).abs() < 0.02);
+        assert!((result.hue.into_degrees() - origin.hue.into_degrees()).abs() < 2.);
+        assert!(result.chroma < origin.chroma);
+    }
+
+    #[test]
+    fn lab_lightness_is_a_percentage() {
+        let ctx = load("button { color: lab(50% 0 0); background-color: lch(50% 0 0); }");
+        let button = ctx.cascade_path("button");
+
+        // CIE lightness 50 is sRGB's mid gray, not the near black a lightness of 0.5 would give
+        assert_eq!(button.color(), Some(Color32::from_gray(0x76)));
+        assert_eq!(button.background_color(), Some(Color32::from_gray(0x76)));
+    }
+
+    #[test]
+    fn lightness_past_the_ends_is_white_or_black() {
+        let ctx = load(
+            "button { color: oklch(120% 0.4 30); background-color: oklch(-10% 0.4 30); }
+             label { color: oklch(50% none none / 50%); }",
+        );
+        let button = ctx.cascade_path("button");
+
+        assert_eq!(button.color(), Some(Color32::WHITE));
+        assert_eq!(button.background_color(), Some(Color32::BLACK));
+        // `none` counts as 0, so this has no chroma
+        let label = ctx.cascade_path("label").color().unwrap();
+        assert!(label.r() == label.g() && label.g() == label.b());
+        assert_eq!(label.a(), 127);
+    }
+}
@@ -0,0 +1,376 @@
+//! Flattening background images into a single color, since egui can only fill a widget with one.
+
+use egui::{lerp, Color32, Rgba};
+use lightningcss::{
+    properties::{
+        custom::{Function, Token, TokenOrValue, UnparsedProperty},
+        Property, PropertyId,
+    },
+    values::{
+        angle::Angle,
+        gradient::{
+            ConicGradient, Gradient, GradientItem, LinearGradient, RadialGradient, WebKitGradient,
+        },
+        image::Image,
+        percentage::DimensionPercentage,
+    },
+};
+
+use super::{
+    composite, eval_calc, without_whitespace, CascadedStyle, CssValueConversions,
+    TokenOrValueIterExt,
+};
+
+/// The average color of a gradient, from its stops and their positions along it (0 to 1, or [None] if the stop doesn't have one).
+///
+/// Positions are fixed up like CSS does, then the gradient is averaged in linear light with premultiplied alpha,
+/// which is roughly what you'd see if you squinted at it. A repeating gradient is averaged over a single repetition.
+/// Interpolation hints are ignored.
+pub fn flatten_gradient(stops: &[(Color32, Option<f32>)], repeating: bool) -> Option<Color32> {
+    let mut positions: Vec<_> = stops.iter().map(|(_, position)| *position).collect();
+    let first = positions.first_mut()?;
+    first.get_or_insert(0.);
+    let last = positions.last_mut()?;
+    last.get_or_insert(1.);
+
+    // A stop can't be before the one in front of it
+    let mut max = f32::NEG_INFINITY;
+    for position in positions.iter_mut().flatten() {
+        max = max.max(*position);
+        *position = max;
+    }
+
+    // Stops without a position are spread evenly between the ones around them
+    let mut start = 0;
+    for end in 1..positions.len() {
+        let (Some(start_position), Some(end_position)) = (positions[start], positions[end]) else {
+            continue;
+        };
+        let count = end - start;
+        for (i, position) in positions[start + 1..end].iter_mut().enumerate() {
+            let t = (i + 1) as f32 / count as f32;
+            *position = Some(lerp(start_position..=end_position, t));
+        }
+        start = end;
+    }
+    let mut positions: Vec<f32> = positions.into_iter().flatten().collect();
+
+    if repeating {
+        let (start, end) = (positions[0], positions[positions.len() - 1]);
+        if end - start <= f32::EPSILON {
+            return stops.last().map(|(color, _)| *color);
+        }
+        for position in &mut positions {
+            *position = (*position - start) / (end - start);
+        }
+    }
+
+    // The gradient is linear between stops and flat past the ends, so the average is the sum of each segment's
+    // average color weighted by how much of 0..1 it covers
+    let colors: Vec<Rgba> = stops.iter().map(|(color, _)| Rgba::from(*color)).collect();
+    let mut points = vec![(positions[0].min(0.), colors[0])];
+    points.extend(positions.iter().copied().zip(colors.iter().copied()));
+    points.push((
+        positions[positions.len() - 1].max(1.),
+        colors[colors.len() - 1],
+    ));
+
+    let mut sum = Rgba::TRANSPARENT;
+    for pair in points.windows(2) {
+        let [(a, color_a), (b, color_b)] = [pair[0], pair[1]];
+        let (start, end) = (a.max(0.), b.min(1.));
+        if end <= start {
+            continue;
+        }
+        let at = |t: f32| color_a * (1. - (t - a) / (b - a)) + color_b * ((t - a) / (b - a));
+        sum = sum + (at(start) + at(end)) * (0.5 * (end - start));
+    }
+
+    Some(Color32::from(sum))
+}
+
+impl CascadedStyle<'_> {
+    /// The color an image flattens to: the average of a gradient, or transparent for things like `url()`s that we can't show anyway.
+    pub fn image_color(&self, image: &Image) -> Color32 {
+        match image {
+            Image::Gradient(gradient) => self.gradient_color(gradient),
+            _ => None,
+        }
+        .unwrap_or(Color32::TRANSPARENT)
+    }
+
+    pub fn gradient_color(&self, gradient: &Gradient) -> Option<Color32> {
+        let position = |position: &DimensionPercentage<_>| {
+            self.lengths
+                .length_percentage(position, self.lengths.box_size)
+                / self.lengths.box_size
+        };
+
+        let stops: Vec<_> = match gradient {
+            Gradient::Linear(LinearGradient { items, .. })
+            | Gradient::RepeatingLinear(LinearGradient { items, .. })
+            | Gradient::Radial(RadialGradient { items, .. })
+            | Gradient::RepeatingRadial(RadialGradient { items, .. }) => items
+                .iter()
+                .filter_map(|item| match item {
+                    GradientItem::ColorStop(stop) => Some((
+                        self.css_color(&stop.color),
+                        stop.position.as_ref().map(position),
+                    )),
+                    GradientItem::Hint(_) => None,
+                })
+                .collect(),
+            Gradient::Conic(ConicGradient { items, .. })
+            | Gradient::RepeatingConic(ConicGradient { items, .. }) => items
+                .iter()
+                .filter_map(|item| match item {
+                    GradientItem::ColorStop(stop) => Some((
+                        self.css_color(&stop.color),
+                        stop.position.as_ref().map(angle_position),
+                    )),
+                    GradientItem::Hint(_) => None,
+                })
+                .collect(),
+            Gradient::WebKitGradient(
+                WebKitGradient::Linear { stops, .. } | WebKitGradient::Radial { stops, .. },
+            ) => stops
+                .iter()
+                .map(|stop| (self.css_color(&stop.color), Some(stop.position)))
+                .collect(),
+        };
+        let repeating = matches!(
+            gradient,
+            Gradient::RepeatingLinear(_)
+                | Gradient::RepeatingRadial(_)
+                | Gradient::RepeatingConic(_)
+        );
+
+        flatten_gradient(&stops, repeating)
+    }
+
+    /// Flattens an image function written as tokens, which is what we get when it uses GTK's colors or is GTK's own,
+    /// like `-gtk-gradient()`. Returns [None] if `function` isn't an image.
+    pub fn eval_image_function(&self, function: &Function) -> Option<Color32> {
+        let args: Vec<_> = without_whitespace(&function.arguments).collect();
+        let mut segments = args.split(|token| matches!(token, TokenOrValue::Token(Token::Comma)));
+
+        let name = function.name.as_ref();
+        let name = name
+            .strip_prefix("-webkit-")
+            .or_else(|| name.strip_prefix("-moz-"))
+            .unwrap_or(name);
+        match name {
+            "linear-gradient"
+            | "radial-gradient"
+            | "conic-gradient"
+            | "repeating-linear-gradient"
+            | "repeating-radial-gradient"
+            | "repeating-conic-gradient" => {
+                let mut stops = Vec::new();
+                for segment in segments {
+                    // The direction or shape, and interpolation hints, don't start with a color
+                    let mut tokens = segment.iter().copied();
+                    let Some(color) = tokens.eval(self).to_color() else {
+                        continue;
+                    };
+                    let mut positions = tokens.filter_map(|token| self.stop_position(token));
+                    stops.push((color, positions.next()));
+                    // `red 10% 20%` is two stops
+                    if let Some(position) = positions.next() {
+                        stops.push((color, Some(position)));
+                    }
+                }
+
+                flatten_gradient(&stops, name.starts_with("repeating-"))
+            }
+            // GTK3's `-gtk-gradient(linear, left top, left bottom, from(@a), color-stop(0.5, @b), to(@c))`
+            "-gtk-gradient" => {
+                let stops: Vec<_> = segments
+                    .filter_map(|segment| {
+                        let [TokenOrValue::Function(stop)] = segment else {
+                            return None;
+                        };
+                        let mut args = without_whitespace(&stop.arguments);
+                        match stop.name.as_ref() {
+                            "from" => Some((args.eval(self).to_color()?, Some(0.))),
+                            "to" => Some((args.eval(self).to_color()?, Some(1.))),
+                            "color-stop" => {
+                                let position =
+                                    args.next().and_then(|token| self.stop_position(token))?;
+                                args.expect_token(Token::Comma)?;
+                                Some((args.eval(self).to_color()?, Some(position)))
+                            }
+                            _ => None,
+                        }
+                    })
+                    .collect();
+
+                flatten_gradient(&stops, false)
+            }
+            // GTK's `image()` is a list of fallbacks, the last of which can be a color. We can't load the rest.
+            "image" => Some(
+                segments
+                    .next_back()
+                    .and_then(|segment| segment.iter().copied().eval(self).to_color())
+                    .unwrap_or(Color32::TRANSPARENT),
+            ),
+
+            _ => None,
+        }
+    }
+
+    /// A gradient stop's position as a fraction of the gradient, with lengths relative to the size of the node.
+    fn stop_position(&self, token: &TokenOrValue) -> Option<f32> {
+        match token {
+            TokenOrValue::Token(Token::Percentage { unit_value, .. }) => Some(*unit_value),
+            TokenOrValue::Token(Token::Number { value, .. }) => Some(*value),
+            TokenOrValue::Length(length) => {
+                Some(self.lengths.length_value(length) / self.lengths.box_size)
+            }
+            TokenOrValue::Angle(angle) => Some(angle.to_degrees() / 360.),
+
+            _ => None,
+        }
+    }
+
+    /// The flattened `background-image`, with every layer painted over the ones below it.
+    pub fn extract_background_image(&self, property: &Property) -> Option<Color32> {
+        let layers = |images: &mut dyn DoubleEndedIterator<Item = &Image>| {
+            images.rev().fold(Color32::TRANSPARENT, |below, image| {
+                composite(self.image_color(image), below)
+            })
+        };
+
+        match property {
+            Property::BackgroundImage(images) => Some(layers(&mut images.iter())),
+            Property::Background(backgrounds) => Some(layers(
+                &mut backgrounds.iter().map(|background| &background.image),
+            )),
+            // The layers are separated by commas, a layer without an image we can read is transparent.
+            // A `background` without an image still resets it.
+            Property::Unparsed(UnparsedProperty {
+                property_id: PropertyId::BackgroundImage | PropertyId::Background,
+                value,
+            }) => {
+                let tokens: Vec<_> = value.0.iter().collect();
+                let images = tokens
+                    .split(|token| matches!(token, TokenOrValue::Token(Token::Comma)))
+                    .map(|layer| {
+                        layer
+                            .iter()
+                            .find_map(|token| match token {
+                                TokenOrValue::Function(function) => {
+                                    self.eval_image_function(function)
+                                }
+                                _ => None,
+                            })
+                            .unwrap_or(Color32::TRANSPARENT)
+                    });
+                Some(
+                    images
+                        .rev()
+                        .fold(Color32::TRANSPARENT, |below, image| composite(image, below)),
+                )
+            }
+
+            _ => None,
+        }
+    }
+}
+
+fn angle_position(position: &DimensionPercentage<Angle>) -> f32 {
+    match position {
+        DimensionPercentage::Dimension(angle) => angle.to_degrees() / 360.,
+        DimensionPercentage::Percentage(percentage) => percentage.0,
+        DimensionPercentage::Calc(calc) => eval_calc(calc, &angle_position),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, Rgba};
+
+    use super::*;
+    use crate::linux::gtk::tests::load;
+
+    #[test]
+    fn gradients_average_in_linear_light() {
+        assert_eq!(
+            flatten_gradient(&[(Color32::WHITE, None), (Color32::BLACK, None)], false),
+            Some(Color32::from(Rgba::from_gray(0.5)))
+        );
+        // A hard stop halfway is half of each color
+        assert_eq!(
+            flatten_gradient(
+                &[(Color32::RED, Some(0.5)), (Color32::BLUE, Some(0.5))],
+                false
+            ),
+            Some(Color32::from(Rgba::from_rgb(0.5, 0., 0.5)))
+        );
+        assert_eq!(flatten_gradient(&[], false), None);
+        assert_eq!(
+            flatten_gradient(&[(Color32::RED, None)], false),
+            Some(Color32::RED)
+        );
+    }
+
+    #[test]
+    fn stops_are_fixed_up_like_css() {
+        // The blue stop can't be before the red one, so it's a hard stop at 75%
+        assert_eq!(
+            flatten_gradient(
+                &[(Color32::RED, Some(0.75)), (Color32::BLUE, Some(0.25))],
+                false
+            ),
+            flatten_gradient(
+                &[(Color32::RED, Some(0.75)), (Color32::BLUE, Some(0.75))],
+                false
+            )
+        );
+        // A repeating gradient is averaged over one repetition
+        assert_eq!(
+            flatten_gradient(
+                &[(Color32::RED, Some(0.)), (Color32::BLUE, Some(0.25))],
+                true
+            ),
+            flatten_gradient(&[(Color32::RED, None), (Color32::BLUE, None)], false)
+        );
+    }
+
+    #[test]
+    fn every_layer_is_painted_over_the_next() {
+        let top = Color32::from_rgba_unmultiplied(255, 0, 0, 128);
+        let flat = |color| flatten_gradient(&[(color, None), (color, None)], false).unwrap();
+        let expected = composite(flat(top), flat(Color32::BLUE));
+
+        // lightningcss can't parse these with GTK's color references in them, so they're flattened from the tokens
+        let ctx = load(
+            "@define-color top rgba(255, 0, 0, 0.5);
+             @define-color bottom #0000ff;
+             button { background-image: linear-gradient(@top, @top), linear-gradient(@bottom, @bottom); }
+             button.flipped { background-image: linear-gradient(@bottom, @bottom), linear-gradient(@top, @top); }
+             button.shorthand { background: linear-gradient(@top, @top), linear-gradient(@bottom, @bottom) @bottom; }",
+        );
+        assert_eq!(
+            ctx.cascade_path("button").background_image(),
+            Some(expected)
+        );
+        assert_eq!(
+            ctx.cascade_path("button.flipped").background_image(),
+            Some(flat(Color32::BLUE))
+        );
+        assert_eq!(
+            ctx.cascade_path("button.shorthand").background_image(),
+            Some(expected)
+        );
+
+        // Typed values work the same
+        let ctx = load(
+            "button { background-image: linear-gradient(rgba(255, 0, 0, 0.5), rgba(255, 0, 0, 0.5)), linear-gradient(blue, blue); }",
+        );
+        assert_eq!(
+            ctx.cascade_path("button").background_image(),
+            Some(expected)
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! A reader for GResource bundles (`.gresource` files), which some themes compile their CSS into.
+//!
+//! These are GVDB files, a simple hash table format from GLib, where each file's value is a `(uuay)` GVariant
+//! holding the file's size, flags and contents. See `gvdb-format.h` and `gresource.c` in GLib for the details.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    ops::Range,
+    path::Path,
+};
+
+use flate2::read::ZlibDecoder;
+
+const SIGNATURE: &[u8; 8] = b"GVariant";
+const HEADER_SIZE: usize = 24;
+const HASH_HEADER_SIZE: usize = 8;
+const HASH_ITEM_SIZE: usize = 24;
+const NO_PARENT: u32 = u32::MAX;
+/// `G_RESOURCE_FLAGS_COMPRESSED`
+const FLAG_COMPRESSED: u32 = 1;
+
+/// The files in a GResource bundle, which you can get with their resource path, like `/org/gnome/theme/gtk.css`.
+#[derive(Debug, Clone, Default)]
+pub struct GResource {
+    data: Vec<u8>,
+    files: HashMap<String, GResourceFile>,
+}
+
+#[derive(Debug, Clone)]
+struct GResourceFile {
+    range: Range<usize>,
+    /// The uncompressed size, only needed for compressed files.
+    size: usize,
+    compressed: bool,
+}
+
+impl GResource {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(fs::read(path)?)
+    }
+
+    pub fn parse(data: Vec<u8>) -> io::Result<Self> {
+        if data.len() < HEADER_SIZE || &data[..8] != SIGNATURE {
+            // A byteswapped signature means a big endian file, which GLib only writes on big endian machines
+            return Err(invalid("not a little endian GVDB file"));
+        }
+
+        let root = read_pointer(&data, 16)?;
+        let table = data
+            .get(root.clone())
+            .ok_or_else(|| invalid("root table out of bounds"))?;
+        if table.len() < HASH_HEADER_SIZE {
+            return Err(invalid("root table too small"));
+        }
+
+        // The bloom filter and buckets are only needed for fast lookups, we just read every item instead
+        let n_bloom_words = (read_u32(table, 0)? & ((1 << 27) - 1)) as usize;
+        let n_buckets = read_u32(table, 4)? as usize;
+        let items_start = HASH_HEADER_SIZE + (n_bloom_words + n_buckets) * 4;
+        let items = table
+            .get(items_start..)
+            .ok_or_else(|| invalid("hash items out of bounds"))?;
+
+        let items = items
+            .chunks_exact(HASH_ITEM_SIZE)
+            .map(|item| {
+                let key_start = read_u32(item, 8)? as usize;
+                let key_size = u16::from_le_bytes([item[12], item[13]]) as usize;
+                let key = data
+                    .get(key_start..key_start + key_size)
+                    .ok_or_else(|| invalid("key out of bounds"))?;
+
+                Ok(HashItem {
+                    parent: read_u32(item, 4)?,
+                    key: String::from_utf8_lossy(key).into_owned(),
+                    kind: item[14],
+                    value: read_pointer(item, 16)?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut files = HashMap::new();
+        for item in &items {
+            // Directories are 'L' items, we only care about the files
+            if item.kind != b'v' {
+                continue;
+            }
+            let Some(name) = full_key(&items, item) else {
+                continue;
+            };
+            let Some(file) = parse_file_value(&data, item.value.clone()) else {
+                continue;
+            };
+            files.insert(name, file);
+        }
+
+        Ok(Self { data, files })
+    }
+
+    /// Gets a file's contents from its resource path, without the `resource://` part.
+    /// Compressed files are decompressed every time, GLib compresses them with zlib.
+    pub fn get(&self, path: &str) -> io::Result<Cow<'_, [u8]>> {
+        let file = self.files.get(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("resource {path} not found"),
+            )
+        })?;
+        let data = &self.data[file.range.clone()];
+        if !file.compressed {
+            return Ok(Cow::Borrowed(data));
+        }
+
+        let mut contents = Vec::with_capacity(file.size);
+        ZlibDecoder::new(data).read_to_end(&mut contents)?;
+        if contents.len() != file.size {
+            return Err(invalid(&format!("{path} doesn't decompress to its size")));
+        }
+        Ok(Cow::Owned(contents))
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    /// Every file path in the bundle.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+}
+
+struct HashItem {
+    parent: u32,
+    key: String,
+    kind: u8,
+    value: Range<usize>,
+}
+
+/// Keys are only stored relative to their parent, so we have to walk up to get the whole path.
+fn full_key(items: &[HashItem], item: &HashItem) -> Option<String> {
+    let mut parts = vec![item.key.as_str()];
+    let mut parent = item.parent;
+    // Guard against cycles in broken files
+    for _ in 0..items.len() {
+        if parent == NO_PARENT {
+            parts.reverse();
+            return Some(parts.concat());
+        }
+        let parent_item = items.get(parent as usize)?;
+        parts.push(&parent_item.key);
+        parent = parent_item.parent;
+    }
+    None
+}
+
+/// File values are a variant (`v`) containing `(uuay)`: the uncompressed size, the flags, then the data.
+fn parse_file_value(data: &[u8], range: Range<usize>) -> Option<GResourceFile> {
+    let value = data.get(range.clone())?;
+    // A serialized variant is the child's data, a nul byte, then the child's type string
+    let separator = value.iter().rposition(|byte| *byte == 0)?;
+    if &value[separator + 1..] != b"(uuay)" {
+        return None;
+    }
+
+    let size = read_u32(value, 0).ok()? as usize;
+    let flags = read_u32(value, 4).ok()?;
+    let compressed = flags & FLAG_COMPRESSED != 0;
+    let contents_start = range.start + 8;
+    let contents_end = range.start + separator;
+    if contents_start > contents_end {
+        return None;
+    }
+
+    Some(GResourceFile {
+        // Uncompressed files have an extra nul byte after their data that isn't part of the file
+        range: if compressed {
+            contents_start..contents_end
+        } else {
+            contents_start..contents_end.min(contents_start + size)
+        },
+        size,
+        compressed,
+    })
+}
+
+fn read_u32(data: &[u8], offset: usize) -> io::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| invalid("unexpected end of data"))
+}
+
+fn read_pointer(data: &[u8], offset: usize) -> io::Result<Range<usize>> {
+    let start = read_u32(data, offset)? as usize;
+    let end = read_u32(data, offset + 4)? as usize;
+    if start > end {
+        return Err(invalid("invalid pointer"));
+    }
+    Ok(start..end)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid gresource: {message}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::*;
+
+    /// A bundle with a `/org/` directory holding each file, written like `glib-compile-resources` does.
+    fn bundle(files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        // (parent, key, kind, value)
+        let mut items = vec![(NO_PARENT, "/org/".to_string(), b'L', Vec::new())];
+        for (name, contents, compressed) in files {
+            let mut value = Vec::new();
+            value.extend((contents.len() as u32).to_le_bytes());
+            value.extend((*compressed as u32).to_le_bytes());
+            if *compressed {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(contents).unwrap();
+                value.extend(encoder.finish().unwrap());
+            } else {
+                value.extend(*contents);
+                value.push(0);
+            }
+            value.push(0);
+            value.extend(b"(uuay)");
+            items.push((0, name.to_string(), b'v', value));
+        }
+
+        let table_start = HEADER_SIZE;
+        let table_end = table_start + HASH_HEADER_SIZE + items.len() * HASH_ITEM_SIZE;
+        let mut data = Vec::new();
+        data.extend(SIGNATURE);
+        data.extend([0; 8]);
+        data.extend((table_start as u32).to_le_bytes());
+        data.extend((table_end as u32).to_le_bytes());
+        // No bloom filter or buckets
+        data.extend([0; HASH_HEADER_SIZE]);
+
+        let mut heap: Vec<u8> = Vec::new();
+        for (parent, key, kind, value) in &items {
+            let key_start = table_end + heap.len();
+            heap.extend(key.as_bytes());
+            let value_start = table_end + heap.len();
+            heap.extend(value);
+
+            data.extend(0u32.to_le_bytes());
+            data.extend(parent.to_le_bytes());
+            data.extend((key_start as u32).to_le_bytes());
+            data.extend((key.len() as u16).to_le_bytes());
+            data.extend([*kind, 0]);
+            data.extend((value_start as u32).to_le_bytes());
+            data.extend(((value_start + value.len()) as u32).to_le_bytes());
+        }
+        data.extend(heap);
+        data
+    }
+
+    #[test]
+    fn files_are_found_by_their_full_path() {
+        let resource = GResource::parse(bundle(&[("gtk.css", b"button {}", false)])).unwrap();
+
+        assert!(resource.contains("/org/gtk.css"));
+        assert!(!resource.contains("gtk.css"));
+        assert!(!resource.contains("/org/"));
+        assert_eq!(&*resource.get("/org/gtk.css").unwrap(), b"button {}");
+        assert_eq!(
+            resource.get("/org/missing.css").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn compressed_files_are_decompressed() {
+        let css = b"window { background: red; }".repeat(8);
+        let resource = GResource::parse(bundle(&[
+            ("gtk.css", &css, true),
+            ("gtk-dark.css", b"window {}", false),
+        ]))
+        .unwrap();
+
+        assert_eq!(&*resource.get("/org/gtk.css").unwrap(), css.as_slice());
+        assert_eq!(&*resource.get("/org/gtk-dark.css").unwrap(), b"window {}");
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(GResource::parse(b"not a gresource at all".to_vec()).is_err());
+        let mut truncated = bundle(&[("gtk.css", b"button {}", false)]);
+        truncated.truncate(HEADER_SIZE + 4);
+        assert!(GResource::parse(truncated).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Turning CSS lengths into pixels, including relative units and `calc()`.
+
+use lightningcss::values::{
+    calc::{Calc, MathFunction, RoundingStrategy},
+    length::{Length, LengthValue},
+    percentage::DimensionPercentage,
+};
+
+/// The size of `Sans 10`, the font GTK falls back to when `gtk-font-name` isn't set, in pixels at 96 DPI.
+pub const DEFAULT_FONT_SIZE: f32 = 10. * 96. / 72.;
+
+/// What relative lengths are resolved against for a node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthContext {
+    /// The node's font size in pixels, for `em` and friends.
+    pub font_size: f32,
+    /// The font size from GTK's settings, for `rem`. GTK uses this instead of the root node's font size.
+    pub root_font_size: f32,
+    /// What percentages of the node's size are relative to, like in `border-radius: 50%`.
+    /// We don't lay anything out, so this is the node's `min-height`, or two lines of text if it doesn't have one.
+    pub box_size: f32,
+}
+
+impl LengthContext {
+    /// The context for a root node, with everything relative to the default font size.
+    pub fn new(font_size: f32) -> Self {
+        Self {
+            font_size,
+            root_font_size: font_size,
+            box_size: font_size * 2.,
+        }
+    }
+
+    pub fn length_value(&self, value: &LengthValue) -> f32 {
+        if let Some(px) = value.to_px() {
+            return px;
+        }
+
+        // We don't have font metrics, so these use the usual rough ratios
+        match *value {
+            LengthValue::Em(v) => v * self.font_size,
+            LengthValue::Rem(v) => v * self.root_font_size,
+            LengthValue::Ex(v) | LengthValue::Ch(v) => v * self.font_size * 0.5,
+            LengthValue::Rex(v) | LengthValue::Rch(v) => v * self.root_font_size * 0.5,
+            LengthValue::Cap(v) => v * self.font_size * 0.7,
+            LengthValue::Rcap(v) => v * self.root_font_size * 0.7,
+            LengthValue::Ic(v) => v * self.font_size,
+            LengthValue::Ric(v) => v * self.root_font_size,
+            LengthValue::Lh(v) => v * self.font_size * 1.2,
+            LengthValue::Rlh(v) => v * self.root_font_size * 1.2,
+            // Viewport and container units don't mean anything for a widget theme
+            _ => 0.,
+        }
+    }
+
+    pub fn length(&self, length: &Length) -> f32 {
+        match length {
+            Length::Value(value) => self.length_value(value),
+            Length::Calc(calc) => eval_calc(calc, &|length| self.length(length)),
+        }
+    }
+
+    /// Resolves a length that can also be a percentage of `percent_of`.
+    pub fn length_percentage(
+        &self,
+        value: &DimensionPercentage<LengthValue>,
+        percent_of: f32,
+    ) -> f32 {
+        match value {
+            DimensionPercentage::Dimension(value) => self.length_value(value),
+            DimensionPercentage::Percentage(percentage) => percentage.0 * percent_of,
+            DimensionPercentage::Calc(calc) => {
+                eval_calc(calc, &|value| self.length_percentage(value, percent_of))
+            }
+        }
+    }
+}
+
+impl Default for LengthContext {
+    fn default() -> Self {
+        Self::new(DEFAULT_FONT_SIZE)
+    }
+}
+
+/// Evaluates a `calc()` tree, with `value` resolving the leaves to pixels. Anything that isn't finite comes out as 0.
+pub fn eval_calc<V>(calc: &Calc<V>, value: &impl Fn(&V) -> f32) -> f32 {
+    let result = match calc {
+        Calc::Value(v) => value(v),
+        Calc::Number(number) => *number,
+        Calc::Sum(a, b) => eval_calc(a, value) + eval_calc(b, value),
+        Calc::Product(factor, v) => factor * eval_calc(v, value),
+        Calc::Function(function) => eval_math_function(function, value),
+    };
+
+    if result.is_finite() {
+        result
+    } else {
+        0.
+    }
+}
+
+fn eval_math_function<V>(function: &MathFunction<V>, value: &impl Fn(&V) -> f32) -> f32 {
+    let eval = |calc: &Calc<V>| eval_calc(calc, value);
+
+    match function {
+        MathFunction::Calc(calc) => eval(calc),
+        MathFunction::Min(calcs) => calcs.iter().map(eval).fold(f32::INFINITY, f32::min),
+        MathFunction::Max(calcs) => calcs.iter().map(eval).fold(f32::NEG_INFINITY, f32::max),
+        // Not f32::clamp, since CSS lets the minimum win when it's bigger than the maximum
+        MathFunction::Clamp(min, v, max) => eval(v).min(eval(max)).max(eval(min)),
+        MathFunction::Round(strategy, v, interval) => {
+            let (v, interval) = (eval(v), eval(interval));
+            let steps = v / interval;
+            let steps = match strategy {
+                RoundingStrategy::Nearest => steps.round(),
+                RoundingStrategy::Up => steps.ceil(),
+                RoundingStrategy::Down => steps.floor(),
+                RoundingStrategy::ToZero => steps.trunc(),
+            };
+            steps * interval
+        }
+        MathFunction::Rem(a, b) => eval(a) % eval(b),
+        MathFunction::Mod(a, b) => {
+            let b = eval(b);
+            (eval(a) % b + b) % b
+        }
+        MathFunction::Abs(v) => eval(v).abs(),
+        MathFunction::Sign(v) => {
+            let v = eval(v);
+            if v == 0. {
+                0.
+            } else {
+                v.signum()
+            }
+        }
+        MathFunction::Hypot(calcs) => calcs
+            .iter()
+            .map(|calc| eval(calc).powi(2))
+            .sum::<f32>()
+            .sqrt(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Rounding;
+
+    use crate::linux::gtk::{
+        tests::{load, load_into},
+        GtkCssParseContext,
+    };
+
+    #[test]
+    fn relative_units_follow_the_inherited_font_size() {
+        let ctx = load_into(
+            GtkCssParseContext {
+                font_size: Some(16.),
+                ..Default::default()
+            },
+            "window { font-size: 2em; }
+             button { min-height: 1.5em; border-width: 0.25rem; border-radius: 1ex; }",
+        );
+        let button = ctx.cascade_path("window > button");
+
+        assert_eq!(button.min_height(), Some(48.));
+        assert_eq!(button.border_width(), Some(4.));
+        assert_eq!(button.border_radius(), Some(Rounding::same(16.)));
+    }
+
+    #[test]
+    fn calc_and_math_functions_resolve_to_pixels() {
+        let ctx = load(
+            "button { min-height: calc(10px + 2 * 3px); border-width: clamp(2px, 10px, 1px); }
+             label { min-height: max(1px, min(20px, 8px)); border-width: round(up, 5px, 4px); }",
+        );
+
+        assert_eq!(ctx.cascade_path("button").min_height(), Some(16.));
+        // The minimum wins when it's bigger than the maximum
+        assert_eq!(ctx.cascade_path("button").border_width(), Some(2.));
+        assert_eq!(ctx.cascade_path("label").min_height(), Some(8.));
+        assert_eq!(ctx.cascade_path("label").border_width(), Some(8.));
+    }
+
+    #[test]
+    fn percentages_are_of_the_min_height() {
+        let ctx = load("button { min-height: 30px; border-radius: calc(50% - 5px); }");
+
+        assert_eq!(
+            ctx.cascade_path("button").border_radius(),
+            Some(Rounding::same(10.))
+        );
+    }
+}
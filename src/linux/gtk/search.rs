@@ -0,0 +1,346 @@
+//! Finding a theme's CSS file the same way GTK does.
+
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+};
+
+use crate::linux::{xdg_data_dirs, xdg_data_home};
+
+/// The newest GTK3 minor version, GTK3 themes can have directories for any even minor version up to this.
+pub const GTK3_MINOR_VERSION: u8 = 24;
+/// The newest GTK4 minor version we know of, see [GTK3_MINOR_VERSION].
+pub const GTK4_MINOR_VERSION: u8 = 20;
+
+/// Every directory that themes are installed in, most important first.
+///
+/// This is the same order GTK searches in: `$XDG_DATA_HOME/themes`, `~/.themes`, each `$XDG_DATA_DIRS/themes`,
+/// and then some paths that aren't always in `$XDG_DATA_DIRS`, like the Flatpak host and NixOS profile directories.
+pub fn theme_dirs() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let mut dirs = Vec::new();
+
+    dirs.extend(xdg_data_home().map(|dir| dir.join("themes")));
+    dirs.extend(home.as_ref().map(|home| home.join(".themes")));
+    dirs.extend(xdg_data_dirs().into_iter().map(|dir| dir.join("themes")));
+
+    // Flatpak apps only see the host's themes through these
+    dirs.push("/run/host/user-share/themes".into());
+    dirs.push("/run/host/share/themes".into());
+    dirs.push("/usr/share/runtime/share/themes".into());
+
+    // NixOS doesn't install anything into /usr/share
+    dirs.extend(
+        home.as_ref()
+            .map(|home| home.join(".nix-profile/share/themes")),
+    );
+    if let Ok(user) = env::var("USER") {
+        dirs.push(
+            Path::new("/etc/profiles/per-user")
+                .join(user)
+                .join("share/themes"),
+        );
+    }
+    dirs.push("/nix/var/nix/profiles/default/share/themes".into());
+    dirs.push("/run/current-system/sw/share/themes".into());
+
+    // GTK's own fallback when XDG_DATA_DIRS doesn't contain it
+    dirs.push("/usr/share/themes".into());
+
+    let mut deduped: Vec<PathBuf> = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        if !deduped.contains(&dir) {
+            deduped.push(dir);
+        }
+    }
+    deduped
+}
+
+/// The versioned subdirectories GTK(version) looks for in a theme, most important first.
+///
+/// GTK tries `gtk-X.Y` for every even minor version from its own down to 0, so a theme can ship
+/// CSS for newer GTK releases next to older ones. GTK3 skips everything between `gtk-3.0` and `gtk-3.14`.
+pub fn version_subdirs(version: u8) -> Vec<String> {
+    let minor = match version {
+        3 => GTK3_MINOR_VERSION,
+        4 => GTK4_MINOR_VERSION,
+        _ => 0,
+    };
+
+    let mut subdirs = Vec::new();
+    let mut i = minor - minor % 2;
+    loop {
+        if version == 3 && i < 14 {
+            i = 0;
+        }
+        subdirs.push(format!("gtk-{version}.{i}"));
+        if i == 0 {
+            break;
+        }
+        i -= 2;
+    }
+
+    subdirs
+}
+
+/// Finds the CSS file for theme `name`, or for its variant (like `dark`) if one is specified.
+///
+/// Just like GTK, every location is searched for the variant before falling back to the theme's plain `gtk.css`.
+/// If nothing is found, the error lists every path that was tried.
+pub fn find_theme_css(name: &str, version: u8, variant: Option<&str>) -> io::Result<PathBuf> {
+    let mut tried = Vec::new();
+    find_theme_css_tried(name, version, variant, &mut tried)
+        .ok_or_else(|| not_found_error(&format!("gtk{version} theme {name}"), &tried))
+}
+
+/// Finds the CSS file for theme `name` in dark or light mode.
+///
+/// Lots of themes ship their dark variant as a separate theme (`Arc-Dark`, `Materia-dark`, `Adwaita-dark`)
+/// instead of a `gtk-dark.css`, so if the theme doesn't have the mode we want, this switches over to the sibling theme that does.
+/// Falls back to the theme itself if there isn't one.
+pub fn find_theme_css_for_mode(name: &str, version: u8, dark: bool) -> io::Result<PathBuf> {
+    let mut tried = Vec::new();
+
+    let found = match (dark, is_dark_theme_name(name)) {
+        (true, false) => {
+            find_theme_css_only(name, version, "gtk-dark.css", &mut tried).or_else(|| {
+                dark_theme_names(name).into_iter().find_map(|sibling| {
+                    find_theme_css_only(&sibling, version, "gtk.css", &mut tried)
+                })
+            })
+        }
+        (false, true) => light_theme_name(name)
+            .and_then(|sibling| find_theme_css_only(&sibling, version, "gtk.css", &mut tried)),
+        _ => None,
+    };
+
+    found
+        .or_else(|| find_theme_css_tried(name, version, dark.then_some("dark"), &mut tried))
+        .ok_or_else(|| {
+            let mode = if dark { "dark" } else { "light" };
+            not_found_error(&format!("gtk{version} theme {name} ({mode})"), &tried)
+        })
+}
+
+/// Whether a theme's name says it's a dark theme, like `Adwaita-dark`, `Arc-Dark` or `Materia-dark-compact`.
+pub fn is_dark_theme_name(name: &str) -> bool {
+    dark_suffix_range(name).is_some()
+}
+
+/// The names a dark sibling of light theme `name` could have, most likely first.
+pub fn dark_theme_names(name: &str) -> Vec<String> {
+    let mut names = vec![
+        format!("{name}-dark"),
+        format!("{name}-Dark"),
+        format!("{name}_dark"),
+    ];
+    // Materia-compact -> Materia-dark-compact
+    if let Some((base, rest)) = name.split_once('-') {
+        names.push(format!("{base}-dark-{rest}"));
+        names.push(format!("{base}-Dark-{rest}"));
+    }
+    names
+}
+
+/// The name of the light sibling of dark theme `name`, if its name says it's dark.
+pub fn light_theme_name(name: &str) -> Option<String> {
+    let range = dark_suffix_range(name)?;
+    let light = format!("{}{}", &name[..range.start], &name[range.end..]);
+    (!light.is_empty()).then_some(light)
+}
+
+/// Where the `-dark` part of a theme name is, either at the end or followed by another `-` part.
+fn dark_suffix_range(name: &str) -> Option<std::ops::Range<usize>> {
+    let lowercase = name.to_ascii_lowercase();
+    ["-dark", "_dark"].into_iter().find_map(|suffix| {
+        lowercase.match_indices(suffix).find_map(|(start, _)| {
+            let end = start + suffix.len();
+            (end == name.len() || name[end..].starts_with('-')).then_some(start..end)
+        })
+    })
+}
+
+/// Searches for the variant's CSS file, then the plain `gtk.css`.
+fn find_theme_css_tried(
+    name: &str,
+    version: u8,
+    variant: Option<&str>,
+    tried: &mut Vec<PathBuf>,
+) -> Option<PathBuf> {
+    variant
+        .map(|variant| format!("gtk-{variant}.css"))
+        .into_iter()
+        .chain(["gtk.css".to_string()])
+        .find_map(|file_name| find_theme_css_only(name, version, &file_name, tried))
+}
+
+/// Searches every theme directory for `file_name` in theme `name`, adding every path it checks to `tried`.
+fn find_theme_css_only(
+    name: &str,
+    version: u8,
+    file_name: &str,
+    tried: &mut Vec<PathBuf>,
+) -> Option<PathBuf> {
+    let subdirs = version_subdirs(version);
+
+    for dir in theme_dirs() {
+        for subdir in &subdirs {
+            let path = dir.join(name).join(subdir).join(file_name);
+            if path.is_file() {
+                return Some(path);
+            }
+            tried.push(path);
+        }
+    }
+
+    None
+}
+
+fn not_found_error(what: &str, tried: &[PathBuf]) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "Could not find a css file for {what}, tried:\n{}",
+            tried
+                .iter()
+                .map(|path| format!("  {}", path.display()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::Lazy;
+
+    /// A `$XDG_DATA_HOME` with a few made-up themes in it, which every test that searches shares.
+    static DATA_HOME: Lazy<PathBuf> = Lazy::new(|| {
+        let data_home = env::temp_dir().join(format!("egui_system_theme_{}", std::process::id()));
+        for file in [
+            "EguiTestVersioned/gtk-4.0/gtk.css",
+            "EguiTestVersioned/gtk-4.6/gtk.css",
+            "EguiTestVersioned/gtk-4.6/gtk-dark.css",
+            "EguiTestVersioned/gtk-4.30/gtk.css",
+            "EguiTestOld/gtk-3.0/gtk.css",
+            "EguiTestOld/gtk-3.12/gtk.css",
+            "EguiTestArc/gtk-4.0/gtk.css",
+            "EguiTestArc-Dark/gtk-4.0/gtk.css",
+            "EguiTestMateria-dark-compact/gtk-4.0/gtk.css",
+            "EguiTestVariant/gtk-4.0/gtk.css",
+            "EguiTestVariant/gtk-4.0/gtk-dark.css",
+            "EguiTestVariant-dark/gtk-4.0/gtk.css",
+        ] {
+            let path = data_home.join("themes").join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        env::set_var("XDG_DATA_HOME", &data_home);
+        data_home
+    });
+
+    fn theme_file(file: &str) -> PathBuf {
+        DATA_HOME.join("themes").join(file)
+    }
+
+    #[test]
+    fn versions_go_from_newest_to_oldest() {
+        assert_eq!(
+            version_subdirs(4)[..3],
+            ["gtk-4.20", "gtk-4.18", "gtk-4.16"]
+        );
+        assert_eq!(version_subdirs(4).last().unwrap(), "gtk-4.0");
+        // GTK3 never had themes for the versions before 3.14
+        assert_eq!(
+            version_subdirs(3)[version_subdirs(3).len() - 2..],
+            ["gtk-3.14", "gtk-3.0"]
+        );
+    }
+
+    #[test]
+    fn theme_dirs_start_with_the_users_and_have_no_duplicates() {
+        let data_home = DATA_HOME.clone();
+        let dirs = theme_dirs();
+
+        assert_eq!(dirs[0], data_home.join("themes"));
+        assert!(dirs.contains(&PathBuf::from("/usr/share/themes")));
+        assert!(dirs
+            .iter()
+            .enumerate()
+            .all(|(i, dir)| !dirs[..i].contains(dir)));
+    }
+
+    #[test]
+    fn the_newest_versioned_css_wins() {
+        Lazy::force(&DATA_HOME);
+        assert_eq!(
+            find_theme_css("EguiTestVersioned", 4, None).unwrap(),
+            theme_file("EguiTestVersioned/gtk-4.6/gtk.css")
+        );
+        assert_eq!(
+            find_theme_css("EguiTestVersioned", 4, Some("dark")).unwrap(),
+            theme_file("EguiTestVersioned/gtk-4.6/gtk-dark.css")
+        );
+        assert_eq!(
+            find_theme_css("EguiTestOld", 3, None).unwrap(),
+            theme_file("EguiTestOld/gtk-3.0/gtk.css")
+        );
+
+        let error = find_theme_css("EguiTestMissing", 4, None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error
+            .to_string()
+            .contains("EguiTestMissing/gtk-4.0/gtk.css"));
+    }
+
+    #[test]
+    fn dark_and_light_names() {
+        assert!(is_dark_theme_name("Adwaita-dark"));
+        assert!(is_dark_theme_name("Materia-dark-compact"));
+        assert!(!is_dark_theme_name("Darkish"));
+        assert!(!is_dark_theme_name("Theme-darker"));
+
+        assert_eq!(light_theme_name("Arc-Dark").as_deref(), Some("Arc"));
+        assert_eq!(
+            light_theme_name("Materia-dark-compact").as_deref(),
+            Some("Materia-compact")
+        );
+        assert_eq!(light_theme_name("Arc"), None);
+        assert!(dark_theme_names("Materia-compact").contains(&"Materia-dark-compact".to_string()));
+    }
+
+    #[test]
+    fn modes_switch_to_sibling_themes() {
+        Lazy::force(&DATA_HOME);
+
+        assert_eq!(
+            find_theme_css_for_mode("EguiTestArc", 4, true).unwrap(),
+            theme_file("EguiTestArc-Dark/gtk-4.0/gtk.css")
+        );
+        assert_eq!(
+            find_theme_css_for_mode("EguiTestArc-Dark", 4, false).unwrap(),
+            theme_file("EguiTestArc/gtk-4.0/gtk.css")
+        );
+        assert_eq!(
+            find_theme_css_for_mode("EguiTestMateria-compact", 4, true).unwrap(),
+            theme_file("EguiTestMateria-dark-compact/gtk-4.0/gtk.css")
+        );
+        // A theme's own dark variant beats a sibling
+        assert_eq!(
+            find_theme_css_for_mode("EguiTestVariant", 4, true).unwrap(),
+            theme_file("EguiTestVariant/gtk-4.0/gtk-dark.css")
+        );
+        // Without a sibling, the theme itself is used
+        assert_eq!(
+            find_theme_css_for_mode("EguiTestVersioned", 4, true).unwrap(),
+            theme_file("EguiTestVersioned/gtk-4.6/gtk-dark.css")
+        );
+        assert_eq!(
+            find_theme_css_for_mode("EguiTestMateria-dark-compact", 4, false).unwrap(),
+            theme_file("EguiTestMateria-dark-compact/gtk-4.0/gtk.css")
+        );
+    }
+}
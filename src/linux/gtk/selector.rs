@@ -0,0 +1,488 @@
+//! Matching selectors against a model of GTK's widget nodes, and working out which declarations win like GTK's cascade does.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use egui::{Color32, Visuals};
+
+use lightningcss::{
+    properties::{
+        custom::{TokenList, UnparsedProperty},
+        Property,
+    },
+    selector::{Combinator, Component, PseudoClass, Selector},
+    values::color::SystemColor,
+};
+
+use super::{
+    composite, compute_custom_properties, contains_var, reparse, substitute_vars,
+    GtkCssParseContext, LengthContext, DEFAULT_FONT_SIZE,
+};
+
+/// A GTK CSS node, with just enough information to match selectors against.
+///
+/// We don't have a real widget tree, so each node only knows its ancestors, not its siblings or children.
+/// Usually made with [WidgetNode::parse], like `WidgetNode::parse("window.background > headerbar > button.flat:hover")`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WidgetNode {
+    /// The CSS name, like `button` or `headerbar`.
+    pub name: String,
+    pub id: Option<String>,
+    /// Style classes, without the `.`.
+    pub classes: Vec<String>,
+    /// Pseudo-class states, without the `:`, like `hover`, `checked` or `backdrop`.
+    pub states: Vec<String>,
+    pub parent: Option<Box<WidgetNode>>,
+}
+
+impl WidgetNode {
+    /// Parses a node path, each node being written like a compound selector (`name#id.class:state`),
+    /// separated by `>` or whitespace. The last node is the one returned, the rest become its ancestors.
+    pub fn parse(path: &str) -> Self {
+        let mut node: Option<Self> = None;
+
+        for compound in path
+            .split(|c: char| c == '>' || c.is_whitespace())
+            .filter(|compound| !compound.is_empty())
+        {
+            let mut child = Self::default();
+            let mut current = String::new();
+            let mut kind = ' ';
+
+            for c in compound.chars().chain(['\0']) {
+                if matches!(c, '.' | ':' | '#' | '\0') {
+                    let part = std::mem::take(&mut current);
+                    match kind {
+                        '.' => child.classes.push(part),
+                        ':' => child.states.push(part),
+                        '#' => child.id = Some(part),
+                        _ => child.name = part,
+                    }
+                    kind = c;
+                } else {
+                    current.push(c);
+                }
+            }
+
+            child.parent = node.map(Box::new);
+            node = Some(child);
+        }
+
+        node.unwrap_or_default()
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes.iter().any(|c| c == class)
+    }
+
+    /// Adds a state to this node and every ancestor, like GTK does with `backdrop` when the window loses focus.
+    pub fn add_state_to_all(&mut self, state: &str) {
+        if !self.has_state(state) {
+            self.states.push(state.to_string());
+        }
+        if let Some(parent) = &mut self.parent {
+            parent.add_state_to_all(state);
+        }
+    }
+
+    pub fn has_state(&self, state: &str) -> bool {
+        self.states.iter().any(|s| s == state)
+    }
+
+    /// Whether `selector` matches this node.
+    pub fn matches(&self, selector: &Selector<'_>) -> bool {
+        let components = selector.iter_raw_match_order().as_slice();
+        matches_compound(components, self)
+    }
+}
+
+/// Prints in the same format [WidgetNode::parse] reads.
+impl fmt::Display for WidgetNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(parent) = &self.parent {
+            write!(f, "{parent} > ")?;
+        }
+        write!(f, "{}", self.name)?;
+        if let Some(id) = &self.id {
+            write!(f, "#{id}")?;
+        }
+        for class in &self.classes {
+            write!(f, ".{class}")?;
+        }
+        for state in &self.states {
+            write!(f, ":{state}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Selectors are stored right to left, so we match the rightmost compound selector against `node`,
+/// then move on to its ancestors depending on the combinator.
+fn matches_compound(components: &[Component<'_>], node: &WidgetNode) -> bool {
+    let (compound, rest) = match components.iter().position(Component::is_combinator) {
+        Some(i) => (
+            &components[..i],
+            Some((&components[i], &components[i + 1..])),
+        ),
+        None => (components, None),
+    };
+
+    if !compound
+        .iter()
+        .all(|component| matches_component(component, node))
+    {
+        return false;
+    }
+
+    match rest {
+        None => true,
+        Some((Component::Combinator(Combinator::Child), rest)) => node
+            .parent
+            .as_deref()
+            .is_some_and(|parent| matches_compound(rest, parent)),
+        Some((Component::Combinator(Combinator::Descendant), rest)) => {
+            let mut ancestor = node.parent.as_deref();
+            while let Some(node) = ancestor {
+                if matches_compound(rest, node) {
+                    return true;
+                }
+                ancestor = node.parent.as_deref();
+            }
+            false
+        }
+        // We don't know about siblings, and GTK doesn't have anything that needs the others
+        _ => false,
+    }
+}
+
+fn matches_component(component: &Component<'_>, node: &WidgetNode) -> bool {
+    match component {
+        Component::ExplicitUniversalType
+        | Component::ExplicitAnyNamespace
+        | Component::ExplicitNoNamespace
+        | Component::DefaultNamespace(_)
+        | Component::Namespace(..) => true,
+        Component::LocalName(name) => name.name.0.as_ref() == node.name,
+        Component::ID(id) => node.id.as_deref() == Some(id.0.as_ref()),
+        Component::Class(class) => node.has_class(class.0.as_ref()),
+        Component::NonTSPseudoClass(pseudo_class) => {
+            pseudo_class_name(pseudo_class).is_some_and(|name| node.has_state(name))
+        }
+        Component::Negation(selectors) => !selectors.iter().any(|selector| node.matches(selector)),
+        Component::Is(selectors) | Component::Where(selectors) | Component::Any(_, selectors) => {
+            selectors.iter().any(|selector| node.matches(selector))
+        }
+        Component::Root => node.parent.is_none(),
+        // Everything else needs siblings, children or things GTK doesn't have, so we play it safe and say it doesn't match,
+        // otherwise `button:first-child` would style every button.
+        _ => false,
+    }
+}
+
+/// The name of a pseudo-class the way GTK writes it, for the ones that can make sense on a GTK widget.
+/// GTK-specific ones like `:backdrop` are [PseudoClass::Custom].
+fn pseudo_class_name<'a>(pseudo_class: &'a PseudoClass<'_>) -> Option<&'a str> {
+    Some(match pseudo_class {
+        PseudoClass::Hover => "hover",
+        PseudoClass::Active => "active",
+        PseudoClass::Focus => "focus",
+        PseudoClass::FocusVisible => "focus-visible",
+        PseudoClass::FocusWithin => "focus-within",
+        PseudoClass::Enabled => "enabled",
+        PseudoClass::Disabled => "disabled",
+        PseudoClass::Checked => "checked",
+        PseudoClass::Indeterminate => "indeterminate",
+        PseudoClass::Link => "link",
+        PseudoClass::Visited => "visited",
+        PseudoClass::ReadOnly(_) => "read-only",
+        PseudoClass::Custom { name } => name.as_ref(),
+        _ => return None,
+    })
+}
+
+/// The declarations that apply to a node, in cascade order: normal declarations sorted by specificity then source order,
+/// followed by the `!important` ones sorted the same way.
+#[derive(Debug, Clone)]
+pub struct CascadedStyle<'a> {
+    pub ctx: &'a GtkCssParseContext,
+    pub declarations: Vec<&'a Property<'static>>,
+    /// The node's custom properties, including inherited ones, with their `var()`s already substituted.
+    pub custom_properties: HashMap<String, TokenList<'static>>,
+    /// What the node's relative lengths are resolved against, with its inherited font size.
+    pub lengths: LengthContext,
+    /// The node's `color`, which is inherited and what `currentColor` resolves to.
+    pub current_color: Color32,
+    /// The opaque color behind the node, from every ancestor's background painted over the next.
+    pub parent_surface: Color32,
+    /// [CascadedStyle::parent_surface] with the node's own background painted over it, which is what its text and borders are drawn on.
+    pub surface: Color32,
+}
+
+impl<'a> CascadedStyle<'a> {
+    /// The value from the winning declaration that `extract` gets a value out of.
+    ///
+    /// Shorthands and longhands are treated the same, so `border-color` after `border` wins for the color,
+    /// but `border` still provides the width. `var()`s are substituted before `extract` sees the declaration.
+    pub fn get<T>(&self, extract: impl Fn(&Self, &Property) -> Option<T>) -> Option<T> {
+        self.declarations
+            .iter()
+            .rev()
+            .find_map(|property| match property {
+                Property::Unparsed(UnparsedProperty { property_id, value })
+                    if contains_var(value) =>
+                {
+                    let value = self.substitute_vars(value)?;
+                    extract(self, &reparse(property_id, value)?)
+                }
+                property => extract(self, property),
+            })
+    }
+
+    /// Substitutes the `var()`s in `tokens` with this node's custom properties.
+    pub fn substitute_vars(&self, tokens: &TokenList<'static>) -> Option<TokenList<'static>> {
+        substitute_vars(tokens, &mut |name| {
+            self.custom_properties.get(name).cloned()
+        })
+    }
+}
+
+impl GtkCssParseContext {
+    /// Collects every declaration that applies to `node`, in cascade order.
+    pub fn cascade(&self, node: &WidgetNode) -> CascadedStyle<'_> {
+        // (important, specificity, rule index, declaration index)
+        let mut matched = Vec::new();
+
+        for (rule_index, rule) in self.rules.iter().enumerate() {
+            let Some(specificity) = rule
+                .selectors
+                .0
+                .iter()
+                .filter(|selector| node.matches(selector))
+                .map(Selector::specificity)
+                .max()
+            else {
+                continue;
+            };
+
+            for (declaration_index, (property, important)) in rule.declarations.iter().enumerate() {
+                matched.push((
+                    (important, specificity, rule_index, declaration_index),
+                    property,
+                ));
+            }
+        }
+
+        matched.sort_by_key(|(key, _)| *key);
+        let declarations: Vec<_> = matched.into_iter().map(|(_, property)| property).collect();
+
+        // Custom properties, font sizes and colors are inherited, so we need the parent's first
+        let inherited = match node.parent.as_deref() {
+            Some(parent) => self.inherited(parent),
+            None => Arc::new(Inherited {
+                custom_properties: HashMap::new(),
+                lengths: LengthContext::new(self.font_size.unwrap_or(DEFAULT_FONT_SIZE)),
+                current_color: self.system_color(&SystemColor::CanvasText),
+                // Something has to be behind the window, so it's egui's own window color
+                surface: if self.dark {
+                    Visuals::dark().window_fill
+                } else {
+                    Visuals::light().window_fill
+                },
+            }),
+        };
+        let custom_properties =
+            compute_custom_properties(declarations.iter().copied(), &inherited.custom_properties);
+        let parent_surface = inherited.surface;
+
+        let mut style = CascadedStyle {
+            ctx: self,
+            declarations,
+            custom_properties,
+            lengths: LengthContext {
+                box_size: inherited.lengths.font_size * 2.,
+                ..inherited.lengths
+            },
+            current_color: inherited.current_color,
+            parent_surface,
+            surface: parent_surface,
+        };
+        // Until these are set, the lengths and color are the parent's, which is what `em` in `font-size`
+        // and `currentColor` in `color` need
+        if let Some(font_size) = style.get(CascadedStyle::extract_font_size) {
+            style.lengths.font_size = font_size;
+            style.lengths.box_size = font_size * 2.;
+        }
+        if let Some(min_height) = style.min_height().filter(|min_height| *min_height > 0.) {
+            style.lengths.box_size = min_height;
+        }
+        if let Some(color) = style.color() {
+            style.current_color = color;
+        }
+        if let Some(fill) = style.background_fill() {
+            style.surface = composite(fill, parent_surface);
+        }
+
+        style
+    }
+
+    /// What `node` passes down to its children, cascaded once and then cached until the rules change.
+    fn inherited(&self, node: &WidgetNode) -> Arc<Inherited> {
+        let key = node.to_string();
+        if let Some(inherited) = self.cascade_cache.get(&key) {
+            return inherited;
+        }
+
+        // The lock isn't held while cascading, since that looks up the node's own ancestors
+        let style = self.cascade(node);
+        let inherited = Arc::new(Inherited {
+            custom_properties: style.custom_properties,
+            lengths: style.lengths,
+            current_color: style.current_color,
+            surface: style.surface,
+        });
+        self.cascade_cache.insert(key, inherited.clone());
+        inherited
+    }
+
+    /// [GtkCssParseContext::cascade] for a node written in the format [WidgetNode::parse] reads.
+    /// If [GtkCssParseContext::backdrop] is set, the node and its ancestors are put in the `:backdrop` state too.
+    pub fn cascade_path(&self, path: &str) -> CascadedStyle<'_> {
+        self.cascade(&self.parse_path(path))
+    }
+
+    /// Whether any rule matches the node at `path` because of `class`, a class it adds to the node.
+    /// Rules that match the node without the class too don't count.
+    pub fn styles_class(&self, path: &str, class: &str) -> bool {
+        let plain = self.parse_path(path);
+        let mut node = plain.clone();
+        node.classes.push(class.to_string());
+
+        self.rules
+            .iter()
+            .flat_map(|rule| &rule.selectors.0)
+            .any(|selector| node.matches(selector) && !plain.matches(selector))
+    }
+
+    fn parse_path(&self, path: &str) -> WidgetNode {
+        let mut node = WidgetNode::parse(path);
+        if self.backdrop {
+            node.add_state_to_all("backdrop");
+        }
+        node
+    }
+}
+
+/// The parts of a [CascadedStyle] its children inherit.
+#[derive(Debug)]
+pub(crate) struct Inherited {
+    custom_properties: HashMap<String, TokenList<'static>>,
+    lengths: LengthContext,
+    current_color: Color32,
+    surface: Color32,
+}
+
+/// What each node cascaded so far passes down to its children, by the node written like [WidgetNode::parse] reads it.
+/// It's only valid for the rules and colors it was cascaded with, so it's cleared whenever those change.
+#[derive(Debug, Default)]
+pub(crate) struct CascadeCache(Mutex<HashMap<String, Arc<Inherited>>>);
+
+impl CascadeCache {
+    fn get(&self, key: &str) -> Option<Arc<Inherited>> {
+        // Nothing can be left half-written in the map, so a poisoned lock is still fine to use
+        let cache = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.get(key).cloned()
+    }
+
+    fn insert(&self, key: String, inherited: Arc<Inherited>) {
+        let mut cache = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.insert(key, inherited);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+/// A copy of a context starts with nothing cached, since it can be changed separately.
+impl Clone for CascadeCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Color32;
+
+    use super::WidgetNode;
+    use crate::linux::gtk::tests::load;
+
+    #[test]
+    fn paths_parse_into_nodes_and_print_back() {
+        let node = WidgetNode::parse("window.background.csd > headerbar button#close.flat:hover");
+
+        assert_eq!(node.name, "button");
+        assert_eq!(node.id.as_deref(), Some("close"));
+        assert_eq!(node.classes, ["flat"]);
+        assert_eq!(node.states, ["hover"]);
+        let headerbar = node.parent.as_deref().unwrap();
+        assert_eq!(headerbar.name, "headerbar");
+        assert_eq!(
+            headerbar.parent.as_deref().unwrap().classes,
+            ["background", "csd"]
+        );
+        assert_eq!(
+            node.to_string(),
+            "window.background.csd > headerbar > button#close.flat:hover"
+        );
+    }
+
+    #[test]
+    fn selectors_match_structurally() {
+        let ctx = load(
+            "window > button { color: #ff0000; }
+             window button.flat { color: #00ff00; }
+             button:not(.flat):hover { color: #0000ff; }
+             button:first-child { background-color: #ff0000; }",
+        );
+
+        assert_eq!(
+            ctx.cascade_path("window > button").color(),
+            Some(Color32::RED)
+        );
+        assert_eq!(ctx.cascade_path("window > box > button").color(), None);
+        assert_eq!(
+            ctx.cascade_path("window > box > button.flat").color(),
+            Some(Color32::GREEN)
+        );
+        assert_eq!(
+            ctx.cascade_path("box > button:hover").color(),
+            Some(Color32::BLUE)
+        );
+        assert_eq!(ctx.cascade_path("box > button.flat:hover").color(), None);
+        // We don't know about siblings, so selectors that need them never match
+        assert_eq!(ctx.cascade_path("button").background_color(), None);
+    }
+
+    #[test]
+    fn declarations_win_by_importance_then_specificity_then_order() {
+        let ctx = load(
+            "button.flat { color: #ff0000; }
+             button { color: #00ff00; }
+             button { background-color: #ff0000 !important; }
+             button.flat { background-color: #00ff00; }
+             button { border-color: #ff0000; border-color: #0000ff; }",
+        );
+        let button = ctx.cascade_path("button.flat");
+
+        assert_eq!(button.color(), Some(Color32::RED));
+        assert_eq!(button.background_color(), Some(Color32::RED));
+        assert_eq!(button.border_color(), Some(Color32::BLUE));
+    }
+}
@@ -0,0 +1,361 @@
+//! GTK's `settings.ini`, read from every place GTK reads it from and merged in the same order.
+
+use std::{collections::HashMap, env, path::PathBuf, process::Command};
+
+use configparser::ini::Ini;
+
+use super::GtkContrast;
+use crate::linux::{xdg_config_dirs, xdg_config_home};
+use crate::Lazy;
+
+/// GNOME's `org.gnome.desktop.interface` settings, read once with a single `gsettings` call since each one spawns a process.
+static GNOME_INTERFACE: Lazy<HashMap<String, String>> = Lazy::new(|| {
+    Command::new("gsettings")
+        .args(["list-recursively", "org.gnome.desktop.interface"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| parse_gsettings_list(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+});
+
+/// A key of GNOME's `org.gnome.desktop.interface`, like `accent-color`, with the quotes around strings removed.
+/// [None] if gsettings isn't there or GNOME is too old to have the key.
+pub(crate) fn gnome_interface_setting(key: &str) -> Option<&'static str> {
+    GNOME_INTERFACE.get(key).map(String::as_str)
+}
+
+/// Parses the `schema key value` lines `gsettings list-recursively` prints.
+fn parse_gsettings_list(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            let (_schema, key, value) = (parts.next()?, parts.next()?, parts.next()?);
+            Some((key.to_string(), value.trim().trim_matches('\'').to_string()))
+        })
+        .collect()
+}
+
+/// The `[Settings]` keys of GTK's `settings.ini` files.
+///
+/// Every field is optional, since GTK only overrides a setting when a file actually sets it.
+/// Use [GtkSettings::load] to get the settings GTK would use for the current user.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GtkSettings {
+    /// `gtk-theme-name`, or the name part of the `GTK_THEME` environment variable.
+    pub theme_name: Option<String>,
+    /// The variant part of `GTK_THEME=Name:variant`, usually `dark`.
+    pub theme_variant: Option<String>,
+    /// `gtk-icon-theme-name`
+    pub icon_theme_name: Option<String>,
+    /// `gtk-font-name`, in the pango format, like `Cantarell 11`.
+    pub font_name: Option<String>,
+    /// `gtk-cursor-theme-name`
+    pub cursor_theme_name: Option<String>,
+    /// `gtk-cursor-theme-size`
+    pub cursor_theme_size: Option<u32>,
+    /// `gtk-cursor-blink`
+    pub cursor_blink: Option<bool>,
+    /// `gtk-cursor-blink-time`, in milliseconds.
+    pub cursor_blink_time: Option<u32>,
+    /// `gtk-application-prefer-dark-theme`
+    pub application_prefer_dark_theme: Option<bool>,
+    /// `gtk-interface-color-scheme`, GTK 4.20's replacement for the above, like `dark` or `light`.
+    pub interface_color_scheme: Option<String>,
+    /// `gtk-interface-contrast`, like `more` or `no-preference`.
+    pub interface_contrast: Option<String>,
+    /// `gtk-decoration-layout`, like `icon:minimize,maximize,close`.
+    pub decoration_layout: Option<String>,
+    /// `gtk-dialogs-use-header`
+    pub dialogs_use_header: Option<bool>,
+    /// `gtk-enable-animations`
+    pub enable_animations: Option<bool>,
+    /// `gtk-enable-event-sounds`
+    pub enable_event_sounds: Option<bool>,
+    /// `gtk-primary-button-warps-slider`
+    pub primary_button_warps_slider: Option<bool>,
+    /// `gtk-xft-dpi`, in 1024ths of a dot per inch, `-1` meaning the default.
+    pub xft_dpi: Option<i32>,
+    /// `gtk-xft-antialias`, `-1` meaning the default.
+    pub xft_antialias: Option<i32>,
+    /// `gtk-xft-hinting`, `-1` meaning the default.
+    pub xft_hinting: Option<i32>,
+    /// `gtk-xft-hintstyle`, like `hintslight`.
+    pub xft_hintstyle: Option<String>,
+    /// `gtk-xft-rgba`, like `rgb` or `none`.
+    pub xft_rgba: Option<String>,
+    /// `gtk-double-click-time`, in milliseconds.
+    pub double_click_time: Option<u32>,
+    /// `gtk-double-click-distance`, in pixels.
+    pub double_click_distance: Option<u32>,
+    /// `gtk-dnd-drag-threshold`, in pixels.
+    pub dnd_drag_threshold: Option<u32>,
+    /// `gtk-overlay-scrolling`, or the `GTK_OVERLAY_SCROLLING` environment variable.
+    pub overlay_scrolling: Option<bool>,
+}
+
+impl GtkSettings {
+    /// Reads the settings GTK(version) would use, lowest priority first:
+    /// `/usr/share/gtk-X.0/settings.ini`, `/etc/gtk-X.0/settings.ini`, each `$XDG_CONFIG_DIRS/gtk-X.0/settings.ini`,
+    /// `$XDG_CONFIG_HOME/gtk-X.0/settings.ini`, and lastly the `GTK_THEME` environment variable.
+    ///
+    /// Files that don't exist are skipped, so this never fails, the settings will just be empty.
+    pub fn load(version: u8) -> Self {
+        let mut settings = Self::default();
+
+        for path in Self::paths(version) {
+            let mut ini = Ini::new();
+            if ini.load(&path).is_ok() {
+                settings.merge_ini(&ini);
+            }
+        }
+
+        if let Ok(gtk_theme) = env::var("GTK_THEME") {
+            settings.merge_gtk_theme_env(&gtk_theme);
+        }
+        // GTK only checks whether it's 0
+        if let Ok(overlay_scrolling) = env::var("GTK_OVERLAY_SCROLLING") {
+            settings.overlay_scrolling = Some(overlay_scrolling.trim() != "0");
+        }
+
+        settings
+    }
+
+    /// Every `settings.ini` path GTK(version) reads, lowest priority first.
+    pub fn paths(version: u8) -> Vec<PathBuf> {
+        let file = format!("gtk-{version}.0/settings.ini");
+        let mut paths = vec![
+            PathBuf::from("/usr/share").join(&file),
+            PathBuf::from("/etc").join(&file),
+        ];
+        // The first directory in XDG_CONFIG_DIRS is the most important one, so it goes last
+        paths.extend(
+            xdg_config_dirs()
+                .into_iter()
+                .rev()
+                .map(|dir| dir.join(&file)),
+        );
+        if let Some(config_home) = xdg_config_home() {
+            paths.push(config_home.join(&file));
+        }
+
+        paths
+    }
+
+    /// Overrides any setting present in the `[Settings]` section of `ini`.
+    pub fn merge_ini(&mut self, ini: &Ini) {
+        let get_string = |key: &str| {
+            ini.get("Settings", key)
+                .map(|value| value.trim().trim_matches('"').to_string())
+        };
+        let get_bool = |key: &str| ini.getboolcoerce("Settings", key).ok().flatten();
+        let get_int = |key: &str| ini.getint("Settings", key).ok().flatten();
+
+        macro_rules! merge {
+            ($field:ident, $value:expr) => {
+                if let Some(value) = $value {
+                    self.$field = Some(value);
+                }
+            };
+        }
+
+        merge!(theme_name, get_string("gtk-theme-name"));
+        merge!(icon_theme_name, get_string("gtk-icon-theme-name"));
+        merge!(font_name, get_string("gtk-font-name"));
+        merge!(cursor_theme_name, get_string("gtk-cursor-theme-name"));
+        merge!(
+            cursor_theme_size,
+            get_int("gtk-cursor-theme-size").and_then(|v| v.try_into().ok())
+        );
+        merge!(cursor_blink, get_bool("gtk-cursor-blink"));
+        merge!(
+            cursor_blink_time,
+            get_int("gtk-cursor-blink-time").and_then(|v| v.try_into().ok())
+        );
+        merge!(
+            application_prefer_dark_theme,
+            get_bool("gtk-application-prefer-dark-theme")
+        );
+        merge!(
+            interface_color_scheme,
+            get_string("gtk-interface-color-scheme")
+        );
+        merge!(interface_contrast, get_string("gtk-interface-contrast"));
+        merge!(decoration_layout, get_string("gtk-decoration-layout"));
+        merge!(dialogs_use_header, get_bool("gtk-dialogs-use-header"));
+        merge!(enable_animations, get_bool("gtk-enable-animations"));
+        merge!(enable_event_sounds, get_bool("gtk-enable-event-sounds"));
+        merge!(
+            primary_button_warps_slider,
+            get_bool("gtk-primary-button-warps-slider")
+        );
+        merge!(
+            xft_dpi,
+            get_int("gtk-xft-dpi").and_then(|v| v.try_into().ok())
+        );
+        merge!(
+            xft_antialias,
+            get_int("gtk-xft-antialias").and_then(|v| v.try_into().ok())
+        );
+        merge!(
+            xft_hinting,
+            get_int("gtk-xft-hinting").and_then(|v| v.try_into().ok())
+        );
+        merge!(xft_hintstyle, get_string("gtk-xft-hintstyle"));
+        merge!(xft_rgba, get_string("gtk-xft-rgba"));
+        merge!(
+            double_click_time,
+            get_int("gtk-double-click-time").and_then(|v| v.try_into().ok())
+        );
+        merge!(
+            double_click_distance,
+            get_int("gtk-double-click-distance").and_then(|v| v.try_into().ok())
+        );
+        merge!(
+            dnd_drag_threshold,
+            get_int("gtk-dnd-drag-threshold").and_then(|v| v.try_into().ok())
+        );
+        merge!(overlay_scrolling, get_bool("gtk-overlay-scrolling"));
+    }
+
+    /// Applies a `GTK_THEME` value, which is either `Name` or `Name:variant`.
+    pub fn merge_gtk_theme_env(&mut self, value: &str) {
+        let (name, variant) = match value.split_once(':') {
+            Some((name, variant)) => (name, Some(variant)),
+            None => (value, None),
+        };
+        if name.is_empty() {
+            return;
+        }
+
+        self.theme_name = Some(name.to_string());
+        self.theme_variant = variant
+            .filter(|variant| !variant.is_empty())
+            .map(str::to_string);
+    }
+
+    /// Whether these settings ask for the dark variant of the theme, either through
+    /// `gtk-application-prefer-dark-theme`, `gtk-interface-color-scheme` or a `GTK_THEME=Name:dark` override.
+    pub fn prefers_dark(&self) -> bool {
+        self.theme_variant.as_deref() == Some("dark")
+            || self.application_prefer_dark_theme == Some(true)
+            || self.interface_color_scheme.as_deref() == Some("dark")
+    }
+
+    /// The size of `gtk-font-name` in pixels, using `gtk-xft-dpi` for sizes in points like GTK does.
+    pub fn font_size_px(&self) -> Option<f32> {
+        // Pango font descriptions end with the size, like `Cantarell Bold 11` or `Cantarell 15px`
+        let size = self.font_name.as_deref()?.split_whitespace().last()?;
+        if let Some(px) = size.strip_suffix("px") {
+            return px.parse().ok();
+        }
+
+        let dpi = self
+            .xft_dpi
+            .filter(|dpi| *dpi > 0)
+            .map_or(96., |dpi| dpi as f32 / 1024.);
+        Some(size.parse::<f32>().ok()? * dpi / 72.)
+    }
+
+    /// Whether scrollbars should float over the content and only show up when it's scrolled or hovered.
+    /// GNOME sets this with `org.gnome.desktop.interface overlay-scrolling` instead of `settings.ini`, and GTK defaults to it.
+    pub fn prefers_overlay_scrolling(&self) -> bool {
+        if let Some(overlay_scrolling) = self.overlay_scrolling {
+            return overlay_scrolling;
+        }
+        gnome_interface_setting("overlay-scrolling") != Some("false")
+    }
+
+    /// The contrast these settings ask for. The HighContrast themes always want more.
+    pub fn prefers_contrast(&self) -> GtkContrast {
+        if self
+            .theme_name
+            .as_deref()
+            .is_some_and(|name| name.starts_with("HighContrast"))
+        {
+            return GtkContrast::More;
+        }
+        self.interface_contrast
+            .as_deref()
+            .and_then(GtkContrast::from_name)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gsettings_lists_are_parsed_without_quotes() {
+        let settings = parse_gsettings_list(
+            "org.gnome.desktop.interface accent-color 'slate'\n\
+             org.gnome.desktop.interface font-name 'Cantarell 11'\n\
+             org.gnome.desktop.interface overlay-scrolling false\n",
+        );
+        assert_eq!(settings["accent-color"], "slate");
+        assert_eq!(settings["font-name"], "Cantarell 11");
+        assert_eq!(settings["overlay-scrolling"], "false");
+    }
+
+    fn ini(contents: &str) -> Ini {
+        let mut ini = Ini::new();
+        ini.read(contents.to_string()).unwrap();
+        ini
+    }
+
+    #[test]
+    fn later_files_only_override_what_they_set() {
+        let mut settings = GtkSettings::default();
+        settings.merge_ini(&ini("[Settings]\n\
+             gtk-theme-name = \"Adwaita\"\n\
+             gtk-font-name = Cantarell 11\n\
+             gtk-application-prefer-dark-theme = true"));
+        settings.merge_ini(&ini("[Settings]\n\
+             gtk-theme-name = Breeze\n\
+             gtk-xft-dpi = 147456"));
+
+        assert_eq!(settings.theme_name.as_deref(), Some("Breeze"));
+        assert_eq!(settings.font_name.as_deref(), Some("Cantarell 11"));
+        assert_eq!(settings.application_prefer_dark_theme, Some(true));
+        // 11pt at 144 DPI
+        assert_eq!(settings.font_size_px(), Some(22.));
+        assert!(settings.prefers_dark());
+    }
+
+    #[test]
+    fn gtk_theme_overrides_the_name_and_variant() {
+        let mut settings = GtkSettings {
+            theme_name: Some("Breeze".to_string()),
+            ..Default::default()
+        };
+
+        settings.merge_gtk_theme_env("Adwaita:dark");
+        assert_eq!(settings.theme_name.as_deref(), Some("Adwaita"));
+        assert!(settings.prefers_dark());
+
+        settings.merge_gtk_theme_env("HighContrast");
+        assert_eq!(settings.theme_variant, None);
+        assert_eq!(settings.prefers_contrast(), GtkContrast::More);
+
+        // An empty name doesn't change anything
+        settings.merge_gtk_theme_env(":dark");
+        assert_eq!(settings.theme_name.as_deref(), Some("HighContrast"));
+        assert_eq!(settings.theme_variant, None);
+    }
+
+    #[test]
+    fn paths_go_from_system_to_user() {
+        let paths = GtkSettings::paths(4);
+
+        assert_eq!(paths[0], PathBuf::from("/usr/share/gtk-4.0/settings.ini"));
+        assert_eq!(paths[1], PathBuf::from("/etc/gtk-4.0/settings.ini"));
+        if let Some(config_home) = xdg_config_home() {
+            assert_eq!(
+                paths.last(),
+                Some(&config_home.join("gtk-4.0/settings.ini"))
+            );
+        }
+    }
+}
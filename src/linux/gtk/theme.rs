@@ -0,0 +1,217 @@
+//! A loaded GTK theme that apps can hold on to, for the colors and rules that don't fit anywhere in egui's style.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use egui::{Color32, Style};
+
+use crate::SystemTheme;
+
+use super::{
+    adwaita_css, find_theme_css_for_mode, is_dark_theme_name, style_gtk_widgets, AdwaitaAccent,
+    CascadedStyle, GResource, GtkContrast, GtkCssParseContext, GtkCssWarning, GtkSettings,
+    WidgetNode, ADWAITA_THEME_NAMES,
+};
+
+/// A GTK theme, loaded the same way [crate::linux::style_gtk] loads one.
+///
+/// Besides styling egui with [GtkTheme::style], it can look up the theme's `@define-color`s and resolve any widget node,
+/// so custom widgets like plots can use colors egui's style has no place for.
+#[derive(Debug, Clone)]
+pub struct GtkTheme {
+    /// The theme's name, like `Adwaita` or `Arc-Dark`.
+    pub name: String,
+    /// The GTK version it was loaded for, 3 or 4.
+    pub version: u8,
+    /// The stylesheet it was loaded from, or [None] for GTK's built-in Adwaita, which this crate bundles.
+    pub path: Option<PathBuf>,
+    ctx: GtkCssParseContext,
+}
+
+impl GtkTheme {
+    /// Loads a theme by name for a GTK version. The font size and contrast come from the user's GTK settings,
+    /// and [dark_light::Mode::Default] goes with the dark mode they prefer, or else whatever the theme calls itself,
+    /// like `Adwaita-dark`.
+    pub fn load(name: &str, version: u8, mode: dark_light::Mode) -> Result<Self, Box<dyn Error>> {
+        let settings = GtkSettings::load(version);
+        let dark = match mode {
+            dark_light::Mode::Dark => true,
+            dark_light::Mode::Light => false,
+            dark_light::Mode::Default => settings.prefers_dark() || is_dark_theme_name(name),
+        };
+
+        Self::load_with(
+            name,
+            version,
+            GtkCssParseContext {
+                dark,
+                contrast: settings.prefers_contrast(),
+                font_size: settings.font_size_px(),
+                overlay_scrolling: settings.prefers_overlay_scrolling(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Like [GtkTheme::load], with a context that already has the mode and settings to load the theme with.
+    pub(crate) fn load_with(
+        name: &str,
+        version: u8,
+        mut ctx: GtkCssParseContext,
+    ) -> Result<Self, Box<dyn Error>> {
+        // GTK always prefers its built-in themes over ones on disk with the same name
+        if ADWAITA_THEME_NAMES.contains(&name) {
+            ctx.dark = ctx.dark || name == "Adwaita-dark";
+            let css = adwaita_css(ctx.dark, AdwaitaAccent::detect().unwrap_or_default());
+            ctx.load_css_str(&css, Path::new("adwaita.css"))?;
+            return Ok(Self {
+                name: name.to_string(),
+                version,
+                path: None,
+                ctx,
+            });
+        }
+
+        let path = find_theme_css_for_mode(name, version, ctx.dark)?;
+
+        // Like GTK, register the theme's resource bundle so its resource:// imports work.
+        // A broken bundle only breaks those imports, which get their own warnings.
+        let resource_path = path.with_file_name("gtk.gresource");
+        if resource_path.is_file() {
+            match GResource::load(&resource_path) {
+                Ok(resource) => ctx.resources.push(resource),
+                Err(err) => ctx.warn(&resource_path, None, err.to_string()),
+            }
+        }
+        ctx.load_css(&path)?;
+
+        Ok(Self {
+            name: name.to_string(),
+            version,
+            path: Some(path),
+            ctx,
+        })
+    }
+
+    /// Keeps the theme's translucent colors as they are, instead of painting them over what's behind each widget, see
+    /// [GtkCssParseContext::keep_alpha]. This applies to [GtkTheme::style] and everything resolved from now on.
+    pub fn keep_alpha(mut self, keep_alpha: bool) -> Self {
+        self.ctx.keep_alpha = keep_alpha;
+        self
+    }
+
+    /// Modifies a style to look like this theme.
+    pub fn style(&self, style: &mut Style) {
+        let mut theme = SystemTheme::new(style.clone());
+        self.style_theme(&mut theme);
+        *style = theme.style;
+    }
+
+    /// Like [GtkTheme::style], with everything that doesn't fit in egui's style too.
+    pub fn style_theme(&self, theme: &mut SystemTheme) {
+        style_gtk_widgets(theme, &self.ctx);
+    }
+
+    /// Whether the theme's dark variant was loaded.
+    pub fn is_dark(&self) -> bool {
+        self.ctx.dark
+    }
+
+    /// The contrast the theme's `@media (prefers-contrast)` rules were matched against.
+    pub fn contrast(&self) -> GtkContrast {
+        self.ctx.contrast
+    }
+
+    /// A color the theme defines with `@define-color`, like `theme_selected_bg_color`, `borders` or `accent_bg_color`.
+    pub fn named_color(&self, name: &str) -> Option<Color32> {
+        self.ctx.defined_colors.get(name).copied()
+    }
+
+    /// Every color the theme defines with `@define-color`, by name.
+    pub fn named_colors(&self) -> &HashMap<String, Color32> {
+        &self.ctx.defined_colors
+    }
+
+    /// Resolves a widget node written like `window.background > button.suggested-action:hover`, see [WidgetNode::parse].
+    /// The result has the declarations that apply to it in cascade order, and getters for the values they resolve to.
+    pub fn resolve(&self, path: &str) -> CascadedStyle<'_> {
+        self.ctx.cascade_path(path)
+    }
+
+    /// Like [GtkTheme::resolve], for a node that's already been built.
+    pub fn resolve_node(&self, node: &WidgetNode) -> CascadedStyle<'_> {
+        self.ctx.cascade(node)
+    }
+
+    /// Everything that went wrong while loading the theme, that didn't stop the rest of it from loading.
+    pub fn warnings(&self) -> &[GtkCssWarning] {
+        &self.ctx.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use egui::Color32;
+
+    use super::*;
+    use crate::VisualsExt;
+
+    fn adwaita(mode: dark_light::Mode) -> GtkTheme {
+        GtkTheme::load("Adwaita", 4, mode).unwrap()
+    }
+
+    #[test]
+    fn bundled_adwaita_loads_in_both_modes() {
+        let accent = Color32::from(AdwaitaAccent::detect().unwrap_or_default());
+
+        for (mode, dark, headerbar) in [
+            (dark_light::Mode::Light, false, Color32::WHITE),
+            (
+                dark_light::Mode::Dark,
+                true,
+                Color32::from_rgb(0x2e, 0x2e, 0x32),
+            ),
+        ] {
+            let theme = adwaita(mode);
+            assert_eq!(theme.is_dark(), dark);
+            assert_eq!(theme.path, None);
+            assert!(theme.warnings().is_empty());
+            assert_eq!(theme.named_color("accent_bg_color"), Some(accent));
+            assert_eq!(theme.named_color("headerbar_bg_color"), Some(headerbar));
+            assert_eq!(
+                theme.resolve("headerbar").background_color(),
+                Some(headerbar)
+            );
+
+            let mut style = Style::default();
+            theme.style(&mut style);
+            assert_eq!(style.visuals.titlebar(true), headerbar);
+        }
+    }
+
+    #[test]
+    fn the_default_mode_follows_the_settings_then_the_name() {
+        assert!(GtkTheme::load("Adwaita-dark", 4, dark_light::Mode::Default)
+            .unwrap()
+            .is_dark());
+
+        // GTK_THEME=Name:dark is one of the settings that asks for dark, but an explicit mode wins over it
+        env::set_var("GTK_THEME", "Adwaita:dark");
+        let (default, light) = (
+            adwaita(dark_light::Mode::Default),
+            adwaita(dark_light::Mode::Light),
+        );
+        env::remove_var("GTK_THEME");
+        assert!(default.is_dark());
+        assert_eq!(
+            default.named_color("headerbar_bg_color"),
+            Some(Color32::from_rgb(0x2e, 0x2e, 0x32))
+        );
+        assert!(!light.is_dark());
+    }
+}
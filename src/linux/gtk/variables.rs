@@ -0,0 +1,270 @@
+//! CSS custom properties (`--name: value`) and `var()`, which newer GTK4 themes use instead of `@define-color`.
+
+use std::collections::{HashMap, HashSet};
+
+use lightningcss::{
+    properties::{
+        custom::{
+            CustomProperty, CustomPropertyName, Function, Token, TokenList, TokenOrValue,
+            UnparsedProperty, UnresolvedColor,
+        },
+        Property, PropertyId,
+    },
+    stylesheet::{ParserOptions, PrinterOptions},
+    traits::IntoOwned,
+};
+
+/// Computes the custom properties of a node, from the ones declared on it (in cascade order) and the ones it inherits.
+///
+/// Like in CSS, `var()`s in the values are substituted here, using the node's own properties and then the inherited ones.
+/// Properties that end up referring to themselves, or to something missing without a fallback, are dropped.
+pub fn compute_custom_properties<'a>(
+    declarations: impl IntoIterator<Item = &'a Property<'static>>,
+    inherited: &HashMap<String, TokenList<'static>>,
+) -> HashMap<String, TokenList<'static>> {
+    let mut declared = HashMap::new();
+    for property in declarations {
+        if let Property::Custom(CustomProperty {
+            name: CustomPropertyName::Custom(name),
+            value,
+        }) = property
+        {
+            declared.insert(name.0.to_string(), value);
+        }
+    }
+
+    let mut computed = inherited.clone();
+    let mut resolved = HashMap::new();
+    for name in declared.keys() {
+        match resolve(
+            name,
+            &declared,
+            inherited,
+            &mut resolved,
+            &mut HashSet::new(),
+        ) {
+            Some(value) => computed.insert(name.clone(), value),
+            None => computed.remove(name),
+        };
+    }
+    computed
+}
+
+fn resolve(
+    name: &str,
+    declared: &HashMap<String, &TokenList<'static>>,
+    inherited: &HashMap<String, TokenList<'static>>,
+    resolved: &mut HashMap<String, Option<TokenList<'static>>>,
+    visiting: &mut HashSet<String>,
+) -> Option<TokenList<'static>> {
+    if let Some(value) = resolved.get(name) {
+        return value.clone();
+    }
+    let Some(value) = declared.get(name) else {
+        return inherited.get(name).cloned();
+    };
+    if !visiting.insert(name.to_string()) {
+        // A cycle, every property in it is invalid
+        return None;
+    }
+
+    let value = substitute_vars(value, &mut |name| {
+        resolve(name, declared, inherited, resolved, visiting)
+    });
+    visiting.remove(name);
+    resolved.insert(name.to_string(), value.clone());
+    value
+}
+
+/// Whether there's a `var()` anywhere in `tokens`.
+pub fn contains_var(tokens: &TokenList) -> bool {
+    tokens.0.iter().any(|token| match token {
+        TokenOrValue::Var(_) => true,
+        TokenOrValue::Function(function) => contains_var(&function.arguments),
+        TokenOrValue::UnresolvedColor(
+            UnresolvedColor::RGB { alpha, .. } | UnresolvedColor::HSL { alpha, .. },
+        ) => contains_var(alpha),
+        TokenOrValue::UnresolvedColor(UnresolvedColor::LightDark { light, dark }) => {
+            contains_var(light) || contains_var(dark)
+        }
+        _ => false,
+    })
+}
+
+/// Replaces every `var()` in `tokens` with what `lookup` returns for its name, or its fallback if that's [None].
+///
+/// Returns [None] if a variable is missing and has no fallback, which makes the whole value invalid like in CSS.
+pub fn substitute_vars(
+    tokens: &TokenList<'static>,
+    lookup: &mut impl FnMut(&str) -> Option<TokenList<'static>>,
+) -> Option<TokenList<'static>> {
+    let mut substituted = Vec::with_capacity(tokens.0.len());
+    let is_whitespace =
+        |token: &TokenOrValue| matches!(token, TokenOrValue::Token(Token::WhiteSpace(_)));
+
+    for (i, token) in tokens.0.iter().enumerate() {
+        match token {
+            TokenOrValue::Var(variable) => {
+                let value = match lookup(variable.name.ident.0.as_ref()) {
+                    Some(value) => value,
+                    None => substitute_vars(variable.fallback.as_ref()?, lookup)?,
+                };
+                // The parser drops the whitespace around a `var()`, but what it stands for is still separate tokens,
+                // so `var(--width) solid` mustn't be printed as `3pxsolid` when it's parsed again
+                if substituted.last().is_some_and(|last| !is_whitespace(last)) {
+                    substituted.push(TokenOrValue::Token(Token::WhiteSpace(" ".into())));
+                }
+                substituted.extend(value.0);
+                if tokens.0.get(i + 1).is_some_and(|next| !is_whitespace(next)) {
+                    substituted.push(TokenOrValue::Token(Token::WhiteSpace(" ".into())));
+                }
+            }
+            TokenOrValue::Function(function) => {
+                substituted.push(TokenOrValue::Function(Function {
+                    name: function.name.clone(),
+                    arguments: substitute_vars(&function.arguments, lookup)?,
+                }))
+            }
+            TokenOrValue::UnresolvedColor(color) => {
+                substituted.push(TokenOrValue::UnresolvedColor(match color {
+                    UnresolvedColor::RGB { r, g, b, alpha } => UnresolvedColor::RGB {
+                        r: *r,
+                        g: *g,
+                        b: *b,
+                        alpha: substitute_vars(alpha, lookup)?,
+                    },
+                    UnresolvedColor::HSL { h, s, l, alpha } => UnresolvedColor::HSL {
+                        h: *h,
+                        s: *s,
+                        l: *l,
+                        alpha: substitute_vars(alpha, lookup)?,
+                    },
+                    UnresolvedColor::LightDark { light, dark } => UnresolvedColor::LightDark {
+                        light: substitute_vars(light, lookup)?,
+                        dark: substitute_vars(dark, lookup)?,
+                    },
+                }))
+            }
+            token => substituted.push(token.clone()),
+        }
+    }
+
+    Some(TokenList(substituted))
+}
+
+/// Parses substituted tokens again as `property_id`, so a `var()` that stood in for a whole value gives us a typed property
+/// (like [Property::BorderRadius]) instead of tokens the extractors can't read.
+pub fn reparse(
+    property_id: &PropertyId<'static>,
+    tokens: TokenList<'static>,
+) -> Option<Property<'static>> {
+    let css = Property::Unparsed(UnparsedProperty {
+        property_id: property_id.clone(),
+        value: tokens,
+    })
+    .value_to_css_string(PrinterOptions::default())
+    .ok()?;
+
+    Property::parse_string(property_id.clone(), &css, ParserOptions::default())
+        .ok()
+        .map(IntoOwned::into_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, Margin, Rounding};
+
+    use crate::linux::gtk::tests::load;
+
+    #[test]
+    fn custom_properties_are_inherited() {
+        let ctx = load(
+            "window { --accent: #ff0000; }
+             button { background-color: var(--accent); color: var(--missing, #00ff00); }",
+        );
+        let button = ctx.cascade_path("window > box > button");
+
+        assert_eq!(button.background_color(), Some(Color32::RED));
+        assert_eq!(button.color(), Some(Color32::GREEN));
+        // Outside of the window, nothing declares it
+        assert_eq!(ctx.cascade_path("button").background_color(), None);
+    }
+
+    #[test]
+    fn later_declarations_override_inherited_ones() {
+        let ctx = load(
+            "window { --accent: #ff0000; }
+             box { --accent: #0000ff; }
+             button { background-color: var(--accent); }",
+        );
+
+        assert_eq!(
+            ctx.cascade_path("window > button").background_color(),
+            Some(Color32::RED)
+        );
+        assert_eq!(
+            ctx.cascade_path("window > box > button").background_color(),
+            Some(Color32::BLUE)
+        );
+    }
+
+    #[test]
+    fn cycles_and_missing_variables_are_invalid() {
+        let ctx = load(
+            "window { --a: var(--b); --b: var(--a); }
+             button { background-color: var(--a, #0000ff); color: var(--missing); }",
+        );
+        let button = ctx.cascade_path("window > button");
+
+        assert_eq!(button.background_color(), Some(Color32::BLUE));
+        assert_eq!(button.color(), None);
+    }
+
+    #[test]
+    fn variables_stay_separate_from_the_tokens_around_them() {
+        let ctx = load(
+            "window { --width: 3px; --style: solid; }
+             button { border: var(--width) var(--style) #ff0000; }",
+        );
+        let button = ctx.cascade_path("window > button");
+
+        assert_eq!(button.border_widths(), Margin::same(3.));
+        assert_eq!(button.border_color(), Some(Color32::RED));
+    }
+
+    #[test]
+    fn whole_values_are_parsed_again() {
+        let ctx = load("window { --radius: 6px; } button { border-radius: var(--radius); }");
+
+        assert_eq!(
+            ctx.cascade_path("window > button").border_radius(),
+            Some(Rounding::same(6.))
+        );
+    }
+
+    #[test]
+    fn define_color_can_use_the_root_properties() {
+        let ctx = load("window { --fg: #112233; } @define-color text_color var(--fg);");
+
+        assert_eq!(
+            ctx.defined_colors.get("text_color"),
+            Some(&Color32::from_rgb(0x11, 0x22, 0x33))
+        );
+    }
+
+    #[test]
+    fn later_stylesheets_change_what_is_inherited() {
+        let mut ctx = load("window { --accent: #ff0000; } button { color: var(--accent); }");
+        assert_eq!(
+            ctx.cascade_path("window > button").color(),
+            Some(Color32::RED)
+        );
+
+        ctx.load_css_str("window { --accent: #0000ff; }", "other.css".as_ref())
+            .unwrap();
+        assert_eq!(
+            ctx.cascade_path("window > button").color(),
+            Some(Color32::BLUE)
+        );
+    }
+}
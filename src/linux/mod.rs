@@ -1,4 +1,9 @@
-use std::{env, error::Error, io, path::Path};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    path::{Path, PathBuf},
+};
 
 use configparser::ini::Ini;
 use epaint::Shadow;
@@ -7,13 +12,24 @@ use crate::*;
 
 mod gtk;
 
+pub use gtk::{
+    find_theme_css, find_theme_css_for_mode, style_adwaita, theme_dirs, AdwaitaAccent,
+    CascadedStyle, GResource, GtkContrast, GtkCssLocation, GtkCssWarning, GtkSettings, GtkTheme,
+    WidgetNode,
+};
+
 pub fn style(style: &mut Style) -> Result<(), Box<dyn Error>> {
+    SystemTheme::style_with(style, style_theme)
+}
+
+/// Like [style], with everything that doesn't fit in egui's style too.
+pub fn style_theme(theme: &mut SystemTheme) -> Result<(), Box<dyn Error>> {
     if env::var("XDG_CURRENT_DESKTOP") == Ok("KDE".to_string())
         || env::var("DESKTOP_SESSION") == Ok("plasma".to_string())
     {
-        style_kde(style)?;
+        style_kde_theme(theme)?;
     } else {
-        style_gtk(style, 4).or_else(|_| style_gtk(style, 3))?;
+        style_gtk_theme(theme, 4).or_else(|_| style_gtk_theme(theme, 3))?;
     }
 
     // DesktopEnvironment::Cinnamon => detect_gtk("/org/cinnamon/desktop/interface/gtk-theme"),
@@ -24,8 +40,14 @@ pub fn style(style: &mut Style) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-#[rustfmt::skip] // I want these macro calls to stay in one line each
 pub fn style_kde(style: &mut Style) -> Result<(), Box<dyn Error>> {
+    SystemTheme::style_with(style, style_kde_theme)
+}
+
+/// Like [style_kde], with everything that doesn't fit in egui's style too.
+#[rustfmt::skip] // I want these macro calls to stay in one line each
+pub fn style_kde_theme(theme: &mut SystemTheme) -> Result<(), Box<dyn Error>> {
+    let style = &mut theme.style;
     // TODO fonts
     let mut kdeglobals = Ini::new();
     kdeglobals.load(Path::new(&env::var("HOME")?).join(".config/kdeglobals"))?;
@@ -83,16 +105,27 @@ pub fn style_kde(style: &mut Style) -> Result<(), Box<dyn Error>> {
     set_color!(style.visuals.selection.bg_fill, "Colors:Selection", "BackgroundAlternate");
     set_stroke!(style.visuals.selection.stroke, "Colors:Selection", "ForegroundNormal", 1.);
 
+    // Menus are drawn on the View colors, egui uses the window fill for them
+    set_color!(style.visuals.window_fill, "Colors:View", "BackgroundNormal");
+    let mut menu_item = style.visuals.widgets.hovered;
+    set_color!(menu_item.weak_bg_fill, "Colors:Selection", "BackgroundNormal");
+    set_color!(menu_item.bg_fill, "Colors:Selection", "BackgroundNormal");
+    set_stroke!(menu_item.fg_stroke, "Colors:Selection", "ForegroundNormal", 1.);
+    theme.menu_items = Some(MenuItemVisuals { hovered: menu_item, context_hovered: menu_item });
+
+    let mut tooltip = crate::TooltipVisuals::from_style(style);
+    set_color!(tooltip.fill, "Colors:Tooltip", "BackgroundNormal");
+    set_color!(tooltip.text_color, "Colors:Tooltip", "ForegroundNormal");
+    // Breeze outlines frames with a mix of their background and text colors
+    tooltip.stroke.color = tooltip.fill.mutate(tooltip.text_color.into(), 0.25);
+
+    let shadow = breeze_shadow();
+    style.visuals.window_shadow = shadow;
+    // Breeze gives menus a smaller shadow than windows
+    style.visuals.popup_shadow = Shadow { offset: shadow.offset / 2., blur: shadow.blur / 2., ..shadow };
+
     // Some arbitrary changes i've hardcoded, since these things couldn't be gotten from kdeglobals
     // In my opinion it makes things look a little nicer when using breeze and the other color themes i have
-    let shadow = Shadow {
-        offset: vec2(0., 10.),
-        blur: 30.,
-        spread: 10.,
-        color: Color32::from_rgba_premultiplied(0, 0, 0, 50),
-    };
-    style.visuals.popup_shadow = shadow;
-    style.visuals.window_shadow = shadow;
 
     style.visuals.widgets.active.expansion = 0.;
     style.visuals.widgets.hovered.expansion = 0.;
@@ -107,49 +140,341 @@ pub fn style_kde(style: &mut Style) -> Result<(), Box<dyn Error>> {
     style.spacing.menu_margin = Margin::same(4.);
     style.spacing.button_padding = vec2(8., 3.);
 
+    let (mut window_bg, mut window_fg) = (style.visuals.panel_fill, style.visuals.text_color());
+    set_color!(window_bg, "Colors:Window", "BackgroundNormal");
+    set_color!(window_fg, "Colors:Window", "ForegroundNormal");
+
+    // Breeze's scroll bars always take up space, with a handle in the window text color at half opacity,
+    // that turns the hover decoration color when hovered. egui only applies the handle opacities to floating bars,
+    // so the bars are floating but allocate their full width and margins, which keeps them out of the content.
+    // egui can only fade the handle's text color in, so it gets whatever opacities come closest to those colors.
+    // ScrollbarLeftClickNavigatesByPage can't be honored, egui always jumps to wherever the track is clicked.
+    let handle = style.visuals.widgets.inactive.fg_stroke.color;
+    let mut hover = style.visuals.widgets.hovered.bg_stroke.color;
+    set_color!(hover, "Colors:View", "DecorationHover");
+    let handle_opacity = opacity_towards(window_bg.mutate(window_fg.into(), 0.5), handle, window_bg);
+    let hover_opacity = opacity_towards(hover, handle, window_bg).max(handle_opacity);
+    let (bar_width, bar_inner_margin, bar_outer_margin) = (8., 3., 3.);
+    style.spacing.scroll = ScrollStyle {
+        floating: true,
+        bar_width,
+        floating_width: bar_width,
+        floating_allocated_width: bar_width + bar_inner_margin + bar_outer_margin,
+        handle_min_length: 20.,
+        bar_inner_margin,
+        bar_outer_margin,
+        foreground_color: true,
+        dormant_handle_opacity: handle_opacity,
+        active_handle_opacity: handle_opacity,
+        interact_handle_opacity: hover_opacity,
+        dormant_background_opacity: 0.,
+        active_background_opacity: 0.,
+        interact_background_opacity: 0.,
+    };
+
+    // Breeze draws grooves with the text color faded into the window, filled with the selection color up to a round handle
+    let mut range = crate::RangeVisuals::from_style(style);
+    range.trough = window_bg.mutate(window_fg.into(), 0.3);
+    range.progress_trough = range.trough;
+    range.spinner = window_fg;
+    set_color!(range.highlight, "Colors:Selection", "BackgroundNormal");
+    set_color!(range.progress, "Colors:Selection", "BackgroundNormal");
+    set_color!(range.handle, "Colors:Button", "DecorationHover");
+    set_color!(range.handle_active, "Colors:Button", "DecorationFocus");
+    range.progress_rounding = Some(Rounding::same(3.));
+    theme.range = Some(range);
+    style.visuals.slider_trailing_fill = true;
+    style.visuals.handle_shape = style::HandleShape::Circle;
+    style.spacing.slider_rail_height = 6.;
+
+    // Menubars in the titlebar are drawn like Breeze's tool buttons on the Header colors
+    let mut menubar = crate::MenubarVisuals::from_style(style);
+    let mut header_bg = menubar.fill;
+    set_color!(header_bg, "Colors:Header", "BackgroundNormal");
+    menubar.fill = header_bg;
+    for widget in [&mut menubar.inactive, &mut menubar.hovered, &mut menubar.active, &mut menubar.open] {
+        set_stroke!(widget.fg_stroke, "Colors:Header", "ForegroundNormal", 1.);
+    }
+    for (widget, key, amount) in [(&mut menubar.hovered, "DecorationHover", 0.2), (&mut menubar.active, "DecorationFocus", 0.3), (&mut menubar.open, "DecorationFocus", 0.3)] {
+        if let Ok(color) = kdeglobals.get_color("Colors:Header", key) {
+            widget.weak_bg_fill = header_bg.mutate(color.into(), amount);
+            widget.bg_fill = widget.weak_bg_fill;
+            widget.bg_stroke = Stroke::new(1., color);
+        }
+    }
+    menubar.button_padding = vec2(6., 4.);
+    menubar.font_size = kdeglobals.get("General", "toolBarFont").as_deref().and_then(qt_font_size_px);
+    theme.menubar = Some(menubar);
+
+    theme.classes = kde_classes(style, &kdeglobals);
+    theme.color_sets = kde_color_sets(&style.visuals, &kdeglobals);
+
+    // Tooltips get the same frame as menus
+    tooltip.rounding = style.visuals.menu_rounding;
+    tooltip.margin = style.spacing.menu_margin;
+    tooltip.shadow = style.visuals.popup_shadow;
+    theme.tooltip = Some(tooltip);
+
     Ok(())
 }
 
-/// Modifies a style to use the current GTK(version) theme.
-pub fn style_gtk(style: &mut Style, version: u8) -> Result<(), Box<dyn Error>> {
-    // TODO fonts
-    let mut gtk_settings = Ini::new();
-    gtk_settings.load(
-        Path::new(&env::var("HOME")?).join(format!(".config/gtk-{version}.0/settings.ini")),
-    )?;
-    let theme_name = gtk_settings
-        .get("Settings", "gtk-theme-name")
-        .ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Could not get gtk-theme-name in gtk4 settings.ini",
-            )
-        })?;
-    let dark_mode = *DARK_LIGHT_MODE != dark_light::Mode::Light;
-    let css_file_name = if dark_mode { "gtk-dark.css" } else { "gtk.css" };
-    let path = [
-        // All paths the css file we're looking for could be, chooses the first one that exists
-        Path::new(&env::var("HOME")?).join(format!(
-            ".themes/{theme_name}/gtk-{version}.0/{css_file_name}"
-        )),
-        Path::new(&env::var("HOME")?).join(format!(".themes/{theme_name}/gtk-{version}.0/gtk.css")), // Fallback if we are in dark mode and gtk-dark.css does not exist
-        format!("/usr/share/themes/{theme_name}/gtk-{version}.0/{css_file_name}").into(),
-        format!("/usr/share/themes/{theme_name}/gtk-{version}.0/gtk.css").into(),
+/// KDE's equivalents of GTK's style classes, from the Selection, View, Window and Complementary color sets.
+fn kde_classes(style: &Style, kdeglobals: &Ini) -> ThemeClasses {
+    let color = |section: &str, key: &str| kdeglobals.get_color(section, key).ok();
+    let base = ClassVisuals::from_style(style);
+    let surface = |section: &str| {
+        Some(base.with_surface(
+            color(section, "BackgroundNormal")?,
+            color(section, "ForegroundNormal")?,
+        ))
+    };
+    // Messages are drawn in the View set's negative, neutral and positive colors
+    let message = |kind: &str| {
+        Some(base.with_surface(
+            color("Colors:View", &format!("Background{kind}"))?,
+            color("Colors:View", &format!("Foreground{kind}"))?,
+        ))
+    };
+    let accent_text = color("Colors:Selection", "ForegroundNormal");
+
+    [
+        (
+            ThemeClass::SuggestedAction,
+            color("Colors:Selection", "BackgroundNormal")
+                .zip(accent_text)
+                .map(|(fill, text)| base.with_button(fill, text)),
+        ),
+        (
+            ThemeClass::DestructiveAction,
+            color("Colors:View", "ForegroundNegative")
+                .zip(accent_text)
+                .map(|(fill, text)| base.with_button(fill, text)),
+        ),
+        (ThemeClass::Card, surface("Colors:View")),
+        (ThemeClass::Sidebar, surface("Colors:Window")),
+        (ThemeClass::View, surface("Colors:View")),
+        (ThemeClass::Osd, surface("Colors:Complementary")),
+        (ThemeClass::Error, message("Negative")),
+        (ThemeClass::Warning, message("Neutral")),
+        (ThemeClass::Success, message("Positive")),
     ]
     .into_iter()
-    .find(|path| path.exists())
-    .ok_or_else(|| {
-        io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Could not find gtk.css or gtk-dark.css file for theme {theme_name}"),
-        )
-    })?;
+    .filter_map(|(class, visuals)| Some((class, visuals?)))
+    .collect()
+}
 
-    gtk::style_gtk_css(style, &path, &mut gtk::GtkCssParseContext::default())?;
+/// The visuals for each [ColorSet] from the color set with the same name, sidebars are drawn with the Window set.
+fn kde_color_sets(visuals: &Visuals, kdeglobals: &Ini) -> HashMap<ColorSet, Visuals> {
+    ColorSet::ALL
+        .into_iter()
+        .filter_map(|set| {
+            let section = match set {
+                ColorSet::Window | ColorSet::Sidebar => "Colors:Window",
+                ColorSet::View => "Colors:View",
+                ColorSet::Header => "Colors:Header",
+                ColorSet::Complementary => "Colors:Complementary",
+            };
+            let background = kdeglobals.get_color(section, "BackgroundNormal").ok()?;
+            let text = kdeglobals.get_color(section, "ForegroundNormal").ok()?;
+            Some((set, crate::color_set_visuals(visuals, background, text)))
+        })
+        .collect()
+}
+
+/// The size of a Qt font description like `Noto Sans,10,-1,5,50,0,0,0,0,0` in pixels.
+/// The second field is the size in points, or -1 if the third one is the size in pixels.
+fn qt_font_size_px(font: &str) -> Option<f32> {
+    let mut fields = font.split(',').skip(1);
+    let points: f32 = fields.next()?.trim().parse().ok()?;
+    if points > 0. {
+        return Some(points * 96. / 72.);
+    }
+    fields
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+        .filter(|pixels| *pixels > 0.)
+}
+
+/// Breeze's window shadow from `breezerc`, which is the closest thing KDE has to a theme shadow.
+/// Breeze's defaults are used if it isn't set up.
+fn breeze_shadow() -> Shadow {
+    let mut breezerc = Ini::new();
+    if let Some(config_home) = xdg_config_home() {
+        let _ = breezerc.load(config_home.join("breezerc"));
+    }
+
+    // Breeze's own shadow sizes
+    let blur = match breezerc.get("Common", "ShadowSize").as_deref() {
+        Some("ShadowNone") => 0.,
+        Some("ShadowSmall") => 16.,
+        Some("ShadowMedium") => 24.,
+        Some("ShadowVeryLarge") => 64.,
+        _ => 48.,
+    };
+    let strength = breezerc
+        .getuint("Common", "ShadowStrength")
+        .ok()
+        .flatten()
+        .unwrap_or(255)
+        .min(255) as f32
+        / 255.;
+    let color = breezerc
+        .get_color("Common", "ShadowColor")
+        .unwrap_or(Color32::BLACK);
+
+    Shadow {
+        offset: vec2(0., blur / 8.),
+        blur,
+        spread: 0.,
+        // Breeze's shadow is spread over a much bigger gradient than egui's, so it's a lot fainter up close
+        color: color.gamma_multiply(strength * 0.25),
+    }
+}
 
+/// How opaque `color` has to be over `background` to look the most like `target`.
+fn opacity_towards(target: Color32, color: Color32, background: Color32) -> f32 {
+    let channels = |color: Color32| [color.r(), color.g(), color.b()].map(f32::from);
+    let (target, color, background) = (channels(target), channels(color), channels(background));
+    let (mut along, mut length) = (0., 0.);
+    for i in 0..3 {
+        along += (target[i] - background[i]) * (color[i] - background[i]);
+        length += (color[i] - background[i]).powi(2);
+    }
+    if length == 0. {
+        return 0.;
+    }
+    (along / length).clamp(0., 1.)
+}
+
+/// The style for when the window isn't focused. KDE doesn't style unfocused windows differently, GTK has `:backdrop`.
+pub fn style_unfocused(style: &mut Style) -> Result<(), Box<dyn Error>> {
+    SystemTheme::style_with(style, style_unfocused_theme)
+}
+
+/// Like [style_unfocused], with everything that doesn't fit in egui's style too.
+pub fn style_unfocused_theme(theme: &mut SystemTheme) -> Result<(), Box<dyn Error>> {
+    if env::var("XDG_CURRENT_DESKTOP") == Ok("KDE".to_string())
+        || env::var("DESKTOP_SESSION") == Ok("plasma".to_string())
+    {
+        style_kde_theme(theme)?;
+    } else {
+        style_gtk_backdrop_theme(theme, 4).or_else(|_| style_gtk_backdrop_theme(theme, 3))?;
+    }
+
+    Ok(())
+}
+
+/// Modifies a style to use the current GTK(version) theme.
+pub fn style_gtk(style: &mut Style, version: u8) -> Result<(), Box<dyn Error>> {
+    SystemTheme::style_with(style, |theme| style_gtk_theme(theme, version))?;
+    Ok(())
+}
+
+/// Like [style_gtk], also returning everything that went wrong while loading the theme, that didn't stop it from loading.
+pub fn style_gtk_with_warnings(
+    style: &mut Style,
+    version: u8,
+) -> Result<Vec<GtkCssWarning>, Box<dyn Error>> {
+    SystemTheme::style_with(style, |theme| style_gtk_theme(theme, version))
+}
+
+/// Like [style_gtk_with_warnings], with everything that doesn't fit in egui's style too.
+pub fn style_gtk_theme(
+    theme: &mut SystemTheme,
+    version: u8,
+) -> Result<Vec<GtkCssWarning>, Box<dyn Error>> {
+    style_gtk_state(theme, version, false)
+}
+
+/// Like [style_gtk], but with every widget in the `:backdrop` state GTK uses for unfocused windows.
+pub fn style_gtk_backdrop(style: &mut Style, version: u8) -> Result<(), Box<dyn Error>> {
+    SystemTheme::style_with(style, |theme| style_gtk_backdrop_theme(theme, version))?;
     Ok(())
 }
 
+/// Like [style_gtk_backdrop], with everything that doesn't fit in egui's style too.
+pub fn style_gtk_backdrop_theme(
+    theme: &mut SystemTheme,
+    version: u8,
+) -> Result<Vec<GtkCssWarning>, Box<dyn Error>> {
+    style_gtk_state(theme, version, true)
+}
+
+fn style_gtk_state(
+    theme: &mut SystemTheme,
+    version: u8,
+    backdrop: bool,
+) -> Result<Vec<GtkCssWarning>, Box<dyn Error>> {
+    // TODO fonts
+    let gtk_settings = GtkSettings::load(version);
+    // GTK falls back to its built-in theme when none is set
+    let theme_name = gtk_settings.theme_name.as_deref().unwrap_or(match version {
+        3 => "Adwaita",
+        _ => "Default",
+    });
+    let dark_mode = gtk_settings.prefers_dark()
+        || match *DARK_LIGHT_MODE {
+            dark_light::Mode::Dark => true,
+            dark_light::Mode::Light => false,
+            // Nothing told us which mode the user wants, so believe whatever the theme calls itself
+            dark_light::Mode::Default => gtk::is_dark_theme_name(theme_name),
+        };
+
+    let ctx = gtk::GtkCssParseContext {
+        dark: dark_mode,
+        contrast: gtk_settings.prefers_contrast(),
+        font_size: gtk_settings.font_size_px(),
+        overlay_scrolling: gtk_settings.prefers_overlay_scrolling(),
+        backdrop,
+        ..Default::default()
+    };
+    let gtk_theme = GtkTheme::load_with(theme_name, version, ctx)?;
+    gtk_theme.style_theme(theme);
+
+    Ok(gtk_theme.warnings().to_vec())
+}
+
+/// `$XDG_CONFIG_HOME`, defaulting to `$HOME/.config`.
+pub(crate) fn xdg_config_home() -> Option<PathBuf> {
+    xdg_dir_var("XDG_CONFIG_HOME")
+        .or_else(|| Some(Path::new(&env::var_os("HOME")?).join(".config")))
+}
+
+/// `$XDG_CONFIG_DIRS`, defaulting to `/etc/xdg`. The most important directory comes first.
+pub(crate) fn xdg_config_dirs() -> Vec<PathBuf> {
+    xdg_dirs_var("XDG_CONFIG_DIRS").unwrap_or_else(|| vec![PathBuf::from("/etc/xdg")])
+}
+
+/// `$XDG_DATA_HOME`, defaulting to `$HOME/.local/share`.
+pub(crate) fn xdg_data_home() -> Option<PathBuf> {
+    xdg_dir_var("XDG_DATA_HOME")
+        .or_else(|| Some(Path::new(&env::var_os("HOME")?).join(".local/share")))
+}
+
+/// `$XDG_DATA_DIRS`, defaulting to `/usr/local/share` and `/usr/share`. The most important directory comes first.
+pub(crate) fn xdg_data_dirs() -> Vec<PathBuf> {
+    xdg_dirs_var("XDG_DATA_DIRS").unwrap_or_else(|| {
+        vec![
+            PathBuf::from("/usr/local/share"),
+            PathBuf::from("/usr/share"),
+        ]
+    })
+}
+
+/// The XDG spec says relative paths should be ignored, same with empty variables.
+fn xdg_dir_var(name: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(env::var_os(name)?);
+    path.is_absolute().then_some(path)
+}
+fn xdg_dirs_var(name: &str) -> Option<Vec<PathBuf>> {
+    let dirs = env::split_paths(&env::var_os(name)?)
+        .filter(|path| path.is_absolute())
+        .collect::<Vec<_>>();
+    (!dirs.is_empty()).then_some(dirs)
+}
+
 pub trait IniExt {
     fn get_color(&self, section: &str, key: &str) -> Result<Color32, std::io::Error>;
 }
@@ -164,12 +489,13 @@ impl IniExt for Ini {
 
         let mut numbers = input.split(',').map(|n| n.trim().parse::<u8>());
         let mut get_number = || {
-            numbers.next().map(Result::ok).flatten().ok_or_else(|| {
+            numbers.next().and_then(Result::ok).ok_or_else(|| {
                 std::io::Error::new(std::io::ErrorKind::InvalidData, format!("color {input}"))
             })
         };
 
-        Ok(Color32::from_rgba_premultiplied(
+        // KDE writes translucent colors as plain RGBA, not premultiplied
+        Ok(Color32::from_rgba_unmultiplied(
             get_number()?,
             get_number()?,
             get_number()?,
@@ -33,6 +33,11 @@ pub fn style(style: &mut Style) -> Result<(), Box<dyn Error>> {
     }
 }
 
+/// Like [style]. macOS has nothing that doesn't fit in egui's style yet.
+pub fn style_theme(theme: &mut SystemTheme) -> Result<(), Box<dyn Error>> {
+    style(&mut theme.style)
+}
+
 #[cfg(not(feature = "dynamic-mac-colors"))]
 /// Simple style with the system accent color and hardcoded values from the OS
 pub(crate) fn static_style(style: &mut Style) {
@@ -1,4 +1,4 @@
-use crate::*;
+use crate::{TooltipVisuals, *};
 use ::windows;
 use epaint::Shadow;
 use windows::Win32::Graphics::Gdi::*;
@@ -32,6 +32,12 @@ pub fn is_windows_eleven() -> bool {
 }
 
 pub fn style(style: &mut Style) -> Result<(), Box<dyn Error>> {
+    SystemTheme::style_with(style, style_theme)
+}
+
+/// Like [style], with everything that doesn't fit in egui's style too.
+pub fn style_theme(theme: &mut SystemTheme) -> Result<(), Box<dyn Error>> {
+    let style = &mut theme.style;
     // See https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getsyscolor#windows-1011-system-colors for color reference
     let window_background = get_color(COLOR_WINDOW);
     let window_text = get_color(COLOR_WINDOWTEXT);
@@ -98,16 +104,34 @@ pub fn style(style: &mut Style) -> Result<(), Box<dyn Error>> {
         style.visuals.menu_rounding = Rounding::ZERO;
     }
 
-    // TODO linux popup shadow and menu rounding and button padding
-
-    let shadow = Shadow {
-        offset: vec2(0., 10.),
-        blur: 30.,
-        spread: 10.,
-        color: Color32::from_rgba_premultiplied(0, 0, 0, 50),
+    // egui draws menus with the window fill, and native ones highlight the hovered item like a selection
+    style.visuals.window_fill = get_color(COLOR_MENU);
+    let menu_item = WidgetVisuals {
+        weak_bg_fill: get_color(COLOR_MENUHILIGHT),
+        bg_fill: get_color(COLOR_MENUHILIGHT),
+        fg_stroke: Stroke::new(1., highlight_text),
+        ..style.visuals.widgets.hovered
+    };
+    theme.menu_items = Some(MenuItemVisuals {
+        hovered: menu_item,
+        context_hovered: menu_item,
+    });
+
+    // Windows 11 uses Fluent's soft shadows, Windows 10 only has a small one
+    let shadow = |offset: f32, blur: f32, alpha: u8| Shadow {
+        offset: vec2(0., offset),
+        blur,
+        spread: 0.,
+        color: Color32::from_black_alpha(alpha),
     };
-    style.visuals.popup_shadow = shadow;
-    style.visuals.window_shadow = shadow;
+    if is_windows_eleven() {
+        style.visuals.popup_shadow = shadow(8., 16., 36);
+        style.visuals.window_shadow = shadow(32., 64., 36);
+        style.visuals.menu_rounding = Rounding::same(8.);
+    } else {
+        style.visuals.popup_shadow = shadow(2., 4., 64);
+        style.visuals.window_shadow = shadow(2., 16., 64);
+    }
 
     style.visuals.widgets.active.expansion = 0.;
     style.visuals.widgets.hovered.expansion = 0.;
@@ -119,5 +143,69 @@ pub fn style(style: &mut Style) -> Result<(), Box<dyn Error>> {
     style.spacing.button_padding = vec2(10., 3.);
     style.spacing.item_spacing = vec2(10., 6.);
 
+    // Windows has no system colors for these, so the messages use Fluent's
+    let [critical, caution, success] = if *DARK_LIGHT_MODE == dark_light::Mode::Dark {
+        [
+            Color32::from_rgb(0xFF, 0x99, 0xA4),
+            Color32::from_rgb(0xFC, 0xE1, 0x00),
+            Color32::from_rgb(0x6C, 0xCB, 0x5F),
+        ]
+    } else {
+        [
+            Color32::from_rgb(0xC4, 0x2B, 0x1C),
+            Color32::from_rgb(0x9D, 0x5D, 0x00),
+            Color32::from_rgb(0x0F, 0x7B, 0x0F),
+        ]
+    };
+    let base = ClassVisuals::from_style(style);
+    let message = |text_color| ClassVisuals { text_color, ..base };
+    let classes = [
+        (
+            ThemeClass::SuggestedAction,
+            base.with_button(highlight, highlight_text),
+        ),
+        (
+            ThemeClass::DestructiveAction,
+            base.with_button(Color32::from_rgb(0xC4, 0x2B, 0x1C), Color32::WHITE),
+        ),
+        (
+            ThemeClass::Card,
+            base.with_surface(window_background, window_text),
+        ),
+        (
+            ThemeClass::Sidebar,
+            base.with_surface(widget_background, widget_text),
+        ),
+        (
+            ThemeClass::View,
+            base.with_surface(window_background, window_text),
+        ),
+        (
+            ThemeClass::Osd,
+            base.with_surface(Color32::from_black_alpha(180), Color32::WHITE),
+        ),
+        (ThemeClass::Error, message(critical)),
+        (ThemeClass::Warning, message(caution)),
+        (ThemeClass::Success, message(success)),
+    ];
+    theme.classes = classes.into_iter().collect();
+
+    let color_sets = [
+        (ColorSet::Window, widget_background_darker, widget_text),
+        (ColorSet::View, window_background, window_text),
+        (ColorSet::Header, window_background, window_text),
+        (ColorSet::Sidebar, widget_background, widget_text),
+    ]
+    .map(|(set, background, text)| (set, color_set_visuals(&style.visuals, background, text)));
+    theme.color_sets = color_sets.into_iter().collect();
+
+    let tooltip = TooltipVisuals {
+        fill: get_color(COLOR_INFOBK),
+        text_color: get_color(COLOR_INFOTEXT),
+        stroke: Stroke::new(1., get_color(COLOR_WINDOWFRAME)),
+        ..TooltipVisuals::from_style(style)
+    };
+    theme.tooltip = Some(tooltip);
+
     Ok(())
 }